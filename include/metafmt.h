@@ -0,0 +1,40 @@
+#ifndef METAFMT_H
+#define METAFMT_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/*
+ * The outcome of a call to metafmt_format. When ok is true, data holds the
+ * formatted output; otherwise it holds an error message. The data is not
+ * NUL-terminated and must be released with metafmt_result_free.
+ */
+typedef struct MetafmtResult {
+    bool ok;
+    uint8_t *data;
+    size_t len;
+} MetafmtResult;
+
+/*
+ * Formats len bytes of UTF-8 input using the formatter for kind, which is
+ * either a path or a filetype name (e.g. "yaml"). options_json may be NULL,
+ * or a JSON object holding the options for each formatter; external
+ * formatters can't be configured. A panic in a formatter is returned as an
+ * error.
+ */
+MetafmtResult metafmt_format(const char *kind, const uint8_t *input, size_t len,
+                             const char *options_json);
+
+/* Releases the memory held by a result returned from metafmt_format. */
+void metafmt_result_free(MetafmtResult result);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
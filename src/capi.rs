@@ -0,0 +1,166 @@
+//! A C API for the formatters. See `include/metafmt.h` for the declarations.
+
+use std::{
+    ffi::{c_char, CStr},
+    panic::{catch_unwind, AssertUnwindSafe},
+    ptr, slice,
+};
+
+/// MetafmtResult holds the outcome of a call to `metafmt_format`. When `ok` is
+/// true, `data` holds the formatted output; otherwise it holds an error
+/// message. The data is not NUL-terminated and must be released with
+/// `metafmt_result_free`.
+#[repr(C)]
+pub struct MetafmtResult {
+    pub ok: bool,
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+impl MetafmtResult {
+    fn new(result: Result<String, String>) -> Self {
+        let (ok, out) = match result {
+            Ok(out) => (true, out),
+            Err(err) => (false, err),
+        };
+        let data = Box::into_raw(out.into_bytes().into_boxed_slice());
+        MetafmtResult {
+            ok,
+            data: data as *mut u8,
+            len: data.len(),
+        }
+    }
+}
+
+/// Formats `len` bytes of UTF-8 input using the formatter for `kind`, which
+/// is either a path or a filetype name (e.g. "yaml"). `options_json` may be
+/// NULL, or a JSON object holding the options for each formatter. A panic
+/// in a formatter is returned as an error instead of unwinding into the
+/// caller.
+///
+/// # Safety
+///
+/// `kind` must be a valid NUL-terminated string, `input` must point to `len`
+/// readable bytes, and `options_json` must be NULL or a valid NUL-terminated
+/// string.
+#[no_mangle]
+pub unsafe extern "C" fn metafmt_format(
+    kind: *const c_char,
+    input: *const u8,
+    len: usize,
+    options_json: *const c_char,
+) -> MetafmtResult {
+    MetafmtResult::new(catch_panic(|| format(kind, input, len, options_json)))
+}
+
+/// Calls `f`, returning a panic as an error so that it doesn't unwind across
+/// the FFI boundary.
+fn catch_panic(f: impl FnOnce() -> Result<String, String>) -> Result<String, String> {
+    catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|_| Err("formatter panicked".to_string()))
+}
+
+unsafe fn format(
+    kind: *const c_char,
+    input: *const u8,
+    len: usize,
+    options_json: *const c_char,
+) -> Result<String, String> {
+    if kind.is_null() {
+        return Err("kind must not be NULL".to_string());
+    }
+    let kind = CStr::from_ptr(kind)
+        .to_str()
+        .map_err(|err| format!("invalid kind: {err}"))?;
+    let input = if input.is_null() {
+        &[]
+    } else {
+        slice::from_raw_parts(input, len)
+    };
+    let input = std::str::from_utf8(input).map_err(|err| format!("invalid input: {err}"))?;
+    let options = if options_json.is_null() {
+        None
+    } else {
+        Some(
+            CStr::from_ptr(options_json)
+                .to_str()
+                .map_err(|err| format!("invalid options: {err}"))?,
+        )
+    };
+    crate::format_with_json_options(kind, input, options)
+}
+
+/// Releases the memory held by a result returned from `metafmt_format`.
+///
+/// # Safety
+///
+/// `result` must have been returned by `metafmt_format` and must not be
+/// freed more than once.
+#[no_mangle]
+pub unsafe extern "C" fn metafmt_result_free(result: MetafmtResult) {
+    if !result.data.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(
+            result.data,
+            result.len,
+        )));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(kind: &CStr, input: &str, options: Option<&CStr>) -> (bool, String) {
+        let options = options.map_or(ptr::null(), CStr::as_ptr);
+        unsafe {
+            let res = metafmt_format(kind.as_ptr(), input.as_ptr(), input.len(), options);
+            let out = slice::from_raw_parts(res.data, res.len).to_vec();
+            let ok = res.ok;
+            metafmt_result_free(res);
+            (ok, String::from_utf8(out).unwrap())
+        }
+    }
+
+    #[test]
+    fn test_format() {
+        let (ok, out) = call(c"yaml", "a:\n    b: c\n", None);
+        assert!(ok);
+        assert_eq!(out, "a:\n  b: c\n");
+    }
+
+    #[test]
+    fn test_format_options() {
+        let (ok, out) = call(c"config.toml", "a = 1\n", Some(c"{\"toml\": {}}"));
+        assert!(ok);
+        assert_eq!(out, "a = 1\n");
+
+        let (ok, out) = call(c"toml", "a = 1\n", Some(c"{\"nope\": {}}"));
+        assert!(!ok);
+        assert!(out.starts_with("invalid options"));
+
+        let external = c"{\"external\": [{\"extensions\": [\"txt\"], \"command\": \"sh\"}]}";
+        let (ok, out) = call(c"a.txt", "", Some(external));
+        assert!(!ok);
+        assert_eq!(
+            out,
+            "invalid options: external formatters are not supported"
+        );
+    }
+
+    #[test]
+    fn test_format_error() {
+        let (ok, _) = call(c"json", "{", None);
+        assert!(!ok);
+        let (ok, out) = call(c"unknown", "", None);
+        assert!(!ok);
+        assert_eq!(out, "unknown format 'unknown'");
+    }
+
+    #[test]
+    fn test_catch_panic() {
+        assert_eq!(catch_panic(|| Ok("a".to_string())), Ok("a".to_string()));
+        assert_eq!(
+            catch_panic(|| panic!("index out of bounds")),
+            Err("formatter panicked".to_string())
+        );
+    }
+}
@@ -0,0 +1,256 @@
+//! Configuration is read from a `.metafmt.toml` file. Each file is formatted
+//! using the nearest configuration file found in its directory or any of its
+//! parent directories; configuration files are not merged.
+//!
+//! ```toml
+//! [[external]]
+//! extensions = ["sh", "bash"]
+//! command = "shfmt"
+//! args = ["-i", "2"]
+//! timeout = 10
+//! ```
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
+
+#[cfg(feature = "markdown")]
+use crate::types::markdown::ProseWrap;
+use crate::types::Formatters;
+
+/// The name of the configuration file.
+pub const FILENAME: &str = ".metafmt.toml";
+
+/// Parses the contents of a configuration file.
+pub fn parse(input: &str) -> Result<Formatters, String> {
+    parse_with(input, &Overrides::default())
+}
+
+fn parse_with(input: &str, overrides: &Overrides) -> Result<Formatters, String> {
+    toml_edit::de::from_str(input)
+        .map(|formatters| overrides.apply(formatters).link())
+        .map_err(|err| err.to_string())
+}
+
+/// Overrides holds options given on the command line, which take precedence
+/// over those of every configuration file.
+#[derive(Clone, Default)]
+pub struct Overrides {
+    /// How the lines of Markdown paragraphs are broken.
+    #[cfg(feature = "markdown")]
+    pub prose_wrap: Option<ProseWrap>,
+    /// The width that Markdown paragraphs are wrapped at.
+    #[cfg(feature = "markdown")]
+    pub prose_width: Option<usize>,
+}
+
+impl Overrides {
+    #[cfg_attr(not(feature = "markdown"), allow(unused_mut))]
+    fn apply(&self, mut formatters: Formatters) -> Formatters {
+        #[cfg(feature = "markdown")]
+        formatters
+            .markdown
+            .set_prose_wrap(self.prose_wrap, self.prose_width);
+        formatters
+    }
+}
+
+/// ConfigError describes a configuration file that could not be loaded.
+#[derive(Debug)]
+pub struct ConfigError {
+    pub path: PathBuf,
+    pub message: String,
+    reported: AtomicBool,
+}
+
+impl ConfigError {
+    /// Returns true the first time it is called, allowing callers to report
+    /// an invalid configuration file once rather than for every file it
+    /// applies to.
+    pub fn should_report(&self) -> bool {
+        !self.reported.swap(true, Ordering::Relaxed)
+    }
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid config {:?}: {}", self.path, self.message)
+    }
+}
+
+type Resolved = Result<Arc<Formatters>, Arc<ConfigError>>;
+
+/// Resolver finds the configuration for files, caching the result for every
+/// directory it visits. It can be shared between threads.
+#[derive(Default)]
+pub struct Resolver {
+    cache: Mutex<HashMap<PathBuf, Resolved>>,
+    overrides: Overrides,
+}
+
+impl Resolver {
+    /// Returns a Resolver that applies the overrides to every configuration.
+    pub fn with_overrides(overrides: Overrides) -> Self {
+        Resolver {
+            overrides,
+            ..Resolver::default()
+        }
+    }
+
+    /// Returns the formatters configured for the provided file.
+    pub fn resolve(&self, path: &Path) -> Resolved {
+        match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => self.resolve_dir(dir),
+            _ => self.resolve_dir(Path::new(".")),
+        }
+    }
+
+    /// Returns the formatters configured for files in the provided directory.
+    pub fn resolve_dir(&self, dir: &Path) -> Resolved {
+        let dir = std::path::absolute(dir).unwrap_or_else(|_| dir.to_path_buf());
+        self.resolve_abs(&dir)
+    }
+
+    fn resolve_abs(&self, dir: &Path) -> Resolved {
+        if let Some(resolved) = self.cache.lock().unwrap().get(dir) {
+            return resolved.clone();
+        }
+
+        let config_path = dir.join(FILENAME);
+        let resolved = if config_path.is_file() {
+            load(&config_path, &self.overrides)
+        } else {
+            match dir.parent() {
+                Some(parent) => self.resolve_abs(parent),
+                None => Ok(Arc::new(self.overrides.apply(Formatters::default()).link())),
+            }
+        };
+
+        self.cache
+            .lock()
+            .unwrap()
+            .insert(dir.to_path_buf(), resolved.clone());
+        resolved
+    }
+}
+
+fn load(path: &Path, overrides: &Overrides) -> Resolved {
+    let result = fs::read_to_string(path)
+        .map_err(|err| err.to_string())
+        .and_then(|input| parse_with(&input, overrides));
+    match result {
+        Ok(mut formatters) => {
+            if let Some(dir) = path.parent() {
+                for external in &mut formatters.external {
+                    external.set_dir(dir);
+                }
+            }
+            Ok(Arc::new(formatters))
+        }
+        Err(message) => Err(Arc::new(ConfigError {
+            path: path.to_path_buf(),
+            message: message.trim_end().to_string(),
+            reported: AtomicBool::new(false),
+        })),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_external() {
+        let config = parse(
+            r#"
+[[external]]
+extensions = ["sh"]
+command = "shfmt"
+args = ["-i", "2"]
+timeout = 5
+"#,
+        )
+        .unwrap();
+        assert_eq!(config.external.len(), 1);
+        assert_eq!(config.external[0].command, "shfmt");
+        assert_eq!(config.external[0].timeout, Some(5));
+        assert!(config.for_extension("sh").is_some());
+        assert!(config.for_extension("bash").is_none());
+    }
+
+    #[test]
+    fn test_parse_unknown_field() {
+        assert!(parse("[nope]\n").is_err());
+    }
+
+    #[test]
+    fn test_resolve_nearest() {
+        let root = std::env::temp_dir().join(format!("metafmt-config-{}", std::process::id()));
+        let nested = root.join("a").join("b");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(
+            root.join(FILENAME),
+            "[[external]]\nextensions = [\"sh\"]\ncommand = \"shfmt\"\n",
+        )
+        .unwrap();
+        fs::write(root.join("a").join(FILENAME), "invalid = \n").unwrap();
+
+        let resolver = Resolver::default();
+        let top = resolver.resolve(&root.join("x.sh")).unwrap();
+        assert_eq!(top.external.len(), 1);
+        assert_eq!(top.external[0].dir.as_deref(), Some(root.as_path()));
+        let Err(err) = resolver.resolve(&nested.join("x.sh")) else {
+            panic!("expected an invalid config error");
+        };
+        assert_eq!(err.path, root.join("a").join(FILENAME));
+        assert!(err.should_report());
+        assert!(!err.should_report());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "markdown")]
+    fn test_resolve_overrides() {
+        use crate::types::Format;
+
+        let root = std::env::temp_dir().join(format!("metafmt-overrides-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        fs::write(
+            root.join(FILENAME),
+            "[markdown]\nprose-wrap = \"always\"\nline-width = 10\n",
+        )
+        .unwrap();
+
+        let input = "one two three four\n";
+        let resolver = Resolver::default();
+        let formatters = resolver.resolve(&root.join("a.md")).unwrap();
+        assert_eq!(
+            formatters.markdown.format(input).unwrap(),
+            "one two\nthree four\n"
+        );
+        let resolver = Resolver::with_overrides(Overrides {
+            prose_width: Some(14),
+            ..Overrides::default()
+        });
+        let formatters = resolver.resolve(&root.join("a.md")).unwrap();
+        assert_eq!(
+            formatters.markdown.format(input).unwrap(),
+            "one two three\nfour\n"
+        );
+        let resolver = Resolver::with_overrides(Overrides {
+            prose_wrap: Some(ProseWrap::Never),
+            ..Overrides::default()
+        });
+        let formatters = resolver.resolve(&root.join("a.md")).unwrap();
+        assert_eq!(formatters.markdown.format(input).unwrap(), input);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -2,8 +2,8 @@ use std::{
     env::temp_dir,
     ffi::OsString,
     fmt::Display,
-    fs::{remove_file, rename, OpenOptions},
-    io::{self, IsTerminal, Read, Result, Write},
+    fs::{remove_file, rename, File, OpenOptions},
+    io::{self, BufRead, BufReader, BufWriter, IsTerminal, Read, Result, Write},
     iter::repeat_with,
     path::{Path, PathBuf},
     str::FromStr,
@@ -16,26 +16,27 @@ use diffy::{create_patch, PatchFormatter};
 use ignore::{overrides::OverrideBuilder, WalkBuilder, WalkState};
 use termcolor::{Buffer, BufferWriter, Color, ColorChoice, ColorSpec, WriteColor};
 
-use crate::types::{json::Json, markdown::Markdown, sql::Sql, toml::Toml, yaml::Yaml, Format};
+use crate::{
+    config::{Overrides, Resolver},
+    types::Format,
+};
 
 #[derive(Default, Clone)]
-pub(crate) struct Options {
-    pub(crate) hidden: bool,
-    pub(crate) globs: Vec<String>,
-    pub(crate) parallel: Option<usize>,
-    pub(crate) diff: bool,
-    pub(crate) list_all: bool,
-    pub(crate) no_ignore: bool,
-    pub(crate) quiet: bool,
-    pub(crate) write: bool,
+pub struct Options {
+    pub hidden: bool,
+    pub globs: Vec<String>,
+    pub parallel: Option<usize>,
+    pub diff: bool,
+    pub list_all: bool,
+    pub no_ignore: bool,
+    pub quiet: bool,
+    pub write: bool,
+    /// Options that take precedence over configuration files.
+    pub overrides: Overrides,
 }
 
-pub(crate) fn format(root: String, ops: Options) -> i32 {
-    let js = Json::default();
-    let md = Markdown::default();
-    let sql = Sql::default();
-    let tm = Toml::default();
-    let ym = Yaml::default();
+pub fn format(root: String, ops: Options) -> i32 {
+    let resolver = Resolver::with_overrides(ops.overrides.clone());
 
     let (tx, rx) = crossbeam::channel::unbounded();
 
@@ -50,6 +51,7 @@ pub(crate) fn format(root: String, ops: Options) -> i32 {
         Some(walk) => walk,
         None => return 1,
     };
+    let resolver = &resolver;
     walkbuilder.build_parallel().run(|| {
         let root = root.clone();
         let ops = ops.clone();
@@ -68,20 +70,23 @@ pub(crate) fn format(root: String, ops: Options) -> i32 {
                 return WalkState::Continue;
             }
             buf.clear();
-            let outcome = match path.extension().and_then(std::ffi::OsStr::to_str) {
-                Some("json") | Some("jsonc") | Some("hjson") | Some("jwcc") => {
-                    check_file(&root, path, &mut in_buf, &mut buf, js, &ops, is_atty)
-                }
-                Some("md") => check_file(&root, path, &mut in_buf, &mut buf, md, &ops, is_atty),
-                Some("sql") => check_file(&root, path, &mut in_buf, &mut buf, sql, &ops, is_atty),
-                Some("toml") => check_file(&root, path, &mut in_buf, &mut buf, tm, &ops, is_atty),
-                Some("yaml") | Some("yml") => {
-                    check_file(&root, path, &mut in_buf, &mut buf, ym, &ops, is_atty)
-                }
-                _ => {
+            let formatters = match resolver.resolve(path) {
+                Ok(formatters) => formatters,
+                Err(err) => {
+                    if err.should_report() {
+                        if !ops.quiet {
+                            print_path_error(&mut buf, &err.path, &err.message);
+                        }
+                        counts.incr_outcome(Outcome::Err);
+                        _ = writer.print(&buf);
+                    }
                     return WalkState::Continue;
                 }
             };
+            let Some(formatter) = formatters.for_path(path) else {
+                return WalkState::Continue;
+            };
+            let outcome = check_file(&root, path, &mut in_buf, &mut buf, formatter, &ops, is_atty);
             counts.incr_outcome(outcome);
             _ = writer.print(&buf);
 
@@ -155,7 +160,7 @@ fn check_file(
     path: &Path,
     in_buf: &mut String,
     buf: &mut Buffer,
-    formatter: impl Format,
+    formatter: &dyn Format,
     ops: &Options,
     is_atty: bool,
 ) -> Outcome {
@@ -163,6 +168,12 @@ fn check_file(
     if ppath.as_os_str().eq_ignore_ascii_case("") {
         ppath = path;
     }
+    // Formats that support streaming are formatted without reading the file
+    // into memory, unless a diff of the whole file is needed.
+    if formatter.supports_streaming() && !ops.diff {
+        return check_file_stream(path, ppath, buf, formatter, ops);
+    }
+
     if let Err(err) = read_file(path, in_buf) {
         if !ops.quiet {
             print_path_error(buf, ppath, &err);
@@ -170,8 +181,15 @@ fn check_file(
         return Outcome::Err;
     }
 
-    let out = match formatter.format(in_buf) {
-        Ok(out) => out,
+    let out = match formatter.format_with_warnings(Some(path), in_buf) {
+        Ok((out, warnings)) => {
+            if !ops.quiet {
+                for warning in warnings {
+                    print_path_warning(buf, ppath, &warning);
+                }
+            }
+            out
+        }
         Err(err) => {
             if !ops.quiet {
                 print_path_error(buf, ppath, &err);
@@ -215,6 +233,114 @@ fn check_file(
     Outcome::Warn
 }
 
+fn check_file_stream(
+    path: &Path,
+    ppath: &Path,
+    buf: &mut Buffer,
+    formatter: &dyn Format,
+    ops: &Options,
+) -> Outcome {
+    let changed = match stream_changed(path, formatter) {
+        Ok(changed) => changed,
+        Err(err) => {
+            if !ops.quiet {
+                print_path_error(buf, ppath, &err);
+            }
+            return Outcome::Err;
+        }
+    };
+
+    if !changed {
+        if !ops.quiet && ops.list_all {
+            _ = buf.set_color(ColorSpec::new().set_fg(Some(Color::Green)).set_bold(true));
+            _ = write!(buf, "info:");
+            _ = buf.reset();
+            _ = writeln!(buf, "  {ppath:?}");
+        }
+        return Outcome::Ok;
+    }
+
+    if ops.write {
+        if let Err(err) = stream_to_file(path, formatter) {
+            if !ops.quiet {
+                print_path_error(buf, ppath, &format!("writing file: {err}"));
+            }
+            return Outcome::Err;
+        }
+    }
+    if !ops.quiet {
+        _ = buf.set_color(ColorSpec::new().set_fg(Some(Color::Yellow)).set_bold(true));
+        _ = write!(buf, "warn:");
+        _ = buf.reset();
+        _ = writeln!(buf, "  {ppath:?}");
+    }
+    Outcome::Warn
+}
+
+/// Formats the file, comparing the output against the original contents as
+/// it is produced, and returns true if they differ.
+fn stream_changed(path: &Path, formatter: &dyn Format) -> std::result::Result<bool, String> {
+    let open = || {
+        File::open(path)
+            .map(BufReader::new)
+            .map_err(|err| err.to_string())
+    };
+    let mut compare = Compare {
+        original: open()?,
+        changed: false,
+    };
+    formatter.format_stream(&mut open()?, &mut compare)?;
+    compare.finish().map_err(|err| err.to_string())
+}
+
+/// Formats the file into a temporary file, which then replaces the original.
+fn stream_to_file(path: &Path, formatter: &dyn Format) -> std::result::Result<(), String> {
+    let mut input = File::open(path)
+        .map(BufReader::new)
+        .map_err(|err| err.to_string())?;
+    let (temp_path, file) = create_temp_file().map_err(|err| err.to_string())?;
+    let mut out = BufWriter::new(file);
+    let result = formatter
+        .format_stream(&mut input, &mut out)
+        .and_then(|_| out.flush().map_err(|err| err.to_string()))
+        .and_then(|_| rename(&temp_path, path).map_err(|err| err.to_string()));
+    if result.is_err() {
+        _ = remove_file(&temp_path);
+    }
+    result
+}
+
+/// Compare is a writer that records whether the data written to it differs
+/// from the contents of the original reader.
+struct Compare<R> {
+    original: R,
+    changed: bool,
+}
+
+impl<R: BufRead> Compare<R> {
+    fn finish(mut self) -> Result<bool> {
+        Ok(self.changed || !self.original.fill_buf()?.is_empty())
+    }
+}
+
+impl<R: BufRead> Write for Compare<R> {
+    fn write(&mut self, mut data: &[u8]) -> Result<usize> {
+        let len = data.len();
+        while !self.changed && !data.is_empty() {
+            let original = self.original.fill_buf()?;
+            let n = original.len().min(data.len());
+            self.changed = n == 0 || original[..n] != data[..n];
+            self.original.consume(n);
+            data = &data[n..];
+        }
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
 fn read_file(path: &Path, buf: &mut String) -> std::io::Result<usize> {
     buf.clear();
     let mut file = std::fs::File::open(path)?;
@@ -238,6 +364,16 @@ fn print_path_error(buf: &mut Buffer, path: &Path, err: &impl Display) {
     _ = buf.reset();
 }
 
+fn print_path_warning(buf: &mut Buffer, path: &Path, warning: &str) {
+    _ = buf.set_color(ColorSpec::new().set_fg(Some(Color::Yellow)).set_bold(true));
+    _ = write!(buf, "warn:");
+    _ = buf.reset();
+    _ = write!(buf, "  {path:?}: ");
+    _ = buf.set_color(ColorSpec::new().set_dimmed(true));
+    _ = writeln!(buf, "{warning}");
+    _ = buf.reset();
+}
+
 fn output(buf: &mut Buffer, counts: Counts, ops: &Options) -> i32 {
     if counts.err == 0 && counts.warn == 0 && counts.ok == 0 {
         _ = writeln!(buf, "No files to format");
@@ -334,13 +470,7 @@ fn write_file(path: &Path, content: &[u8]) -> Result<()> {
 }
 
 fn write_to_temp_file(content: &[u8]) -> Result<PathBuf> {
-    let name: String = repeat_with(fastrand::alphanumeric).take(16).collect();
-    let name = OsString::from_str(&name).unwrap();
-    let temp_path = temp_dir().join(name);
-    let mut file = OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open(&temp_path)?;
+    let (temp_path, mut file) = create_temp_file()?;
     if let Err(err) = file.write(content) {
         _ = remove_file(&temp_path);
         Err(err)
@@ -348,3 +478,14 @@ fn write_to_temp_file(content: &[u8]) -> Result<PathBuf> {
         Ok(temp_path)
     }
 }
+
+fn create_temp_file() -> Result<(PathBuf, File)> {
+    let name: String = repeat_with(fastrand::alphanumeric).take(16).collect();
+    let name = OsString::from_str(&name).unwrap();
+    let temp_path = temp_dir().join(name);
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&temp_path)?;
+    Ok((temp_path, file))
+}
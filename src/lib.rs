@@ -0,0 +1,62 @@
+//! metafmt is a formatter for configuration and text files.
+//!
+//! Every formatter implements the [`Format`] trait and can be used directly,
+//! or looked up by file type with [`Formatters`]. The [`format_str`] function
+//! formats a string given a path or filetype name, and [`fmt::format`] walks
+//! a directory tree, checking or rewriting every file it knows how to format.
+
+#[cfg(feature = "capi")]
+pub mod capi;
+pub mod config;
+#[cfg(feature = "cli")]
+pub mod fmt;
+pub mod types;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+use std::path::Path;
+
+pub use types::{Format, Formatters, Kind};
+
+/// Formats the input using the formatter for the provided path or filetype
+/// name (e.g. "config.yaml", "yaml" or "markdown").
+pub fn format_str(
+    path_or_kind: &str,
+    input: &str,
+    formatters: &Formatters,
+) -> Result<String, String> {
+    if let Some(formatter) = formatters.for_name(path_or_kind) {
+        return formatter.format_file(&types::path_for_name(path_or_kind), input);
+    }
+    // Unlike the directory walker, a path that is provided explicitly is
+    // reported if its format's cargo feature is disabled.
+    let path = Path::new(path_or_kind);
+    formatters
+        .for_path(path)
+        .or_else(|| Kind::from_path(path).map(|kind| formatters.get(kind)))
+        .ok_or_else(|| format!("unknown format '{path_or_kind}'"))?
+        .format_file(path, input)
+}
+
+/// Formats the input, deserializing the options for each formatter from an
+/// optional JSON object. External formatters are rejected, so that callers
+/// of the bindings can't run commands.
+#[cfg(any(feature = "capi", feature = "wasm"))]
+fn format_with_json_options(
+    path_or_kind: &str,
+    input: &str,
+    options: Option<&str>,
+) -> Result<String, String> {
+    let formatters = match options {
+        Some(options) => {
+            let formatters: Formatters =
+                serde_json::from_str(options).map_err(|err| format!("invalid options: {err}"))?;
+            if !formatters.external.is_empty() {
+                return Err("invalid options: external formatters are not supported".to_string());
+            }
+            formatters.link()
+        }
+        None => Formatters::default(),
+    };
+    format_str(path_or_kind, input, &formatters)
+}
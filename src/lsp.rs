@@ -0,0 +1,577 @@
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use serde_json::{json, Value};
+
+use metafmt::{
+    config::{self, ConfigError, Resolver},
+    types::{Format, Formatters},
+};
+
+type Error = Box<dyn std::error::Error>;
+
+static VERSION: &str = env!("CARGO_PKG_VERSION");
+
+const TEXT_DOCUMENT_SYNC_FULL: u8 = 1;
+const SEVERITY_ERROR: u8 = 1;
+const SEVERITY_WARNING: u8 = 2;
+const METHOD_NOT_FOUND: i32 = -32601;
+const INVALID_REQUEST: i32 = -32600;
+
+/// Runs a Language Server Protocol server over stdin/stdout until the client
+/// sends the 'exit' notification or closes the input stream.
+pub(crate) fn run() -> i32 {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let mut server = Server::new(stdout.lock());
+    let mut reader = stdin.lock();
+
+    loop {
+        let msg = match read_message(&mut reader) {
+            Ok(Some(msg)) => msg,
+            Ok(None) => return 1,
+            Err(err) => {
+                eprintln!("error: {err}");
+                return 1;
+            }
+        };
+        match server.handle(msg) {
+            Ok(Some(code)) => return code,
+            Ok(None) => {}
+            Err(err) => {
+                eprintln!("error: {err}");
+                return 1;
+            }
+        }
+    }
+}
+
+struct Server<W: Write> {
+    out: W,
+    resolver: Resolver,
+    documents: HashMap<String, Document>,
+    shutdown: bool,
+}
+
+struct Document {
+    path: Option<PathBuf>,
+    language_id: String,
+    text: String,
+    formatters: Result<Arc<Formatters>, Arc<ConfigError>>,
+}
+
+impl Document {
+    /// Formats the text of the document, or a fragment of it, as the file
+    /// at the document's path if it has one.
+    fn format(&self, formatter: &dyn Format, text: &str) -> Result<String, String> {
+        match &self.path {
+            Some(path) => formatter.format_file(path, text),
+            None => formatter.format(text),
+        }
+    }
+
+    /// Returns the formatter for the document, or None if metafmt does not
+    /// know how to format it.
+    fn formatter(&self) -> Result<Option<&dyn Format>, String> {
+        let formatters = self.formatters.as_ref().map_err(|err| err.to_string())?;
+        Ok(self
+            .path
+            .as_deref()
+            .and_then(|path| formatters.for_path(path))
+            .or_else(|| formatters.for_embedded(&self.language_id)))
+    }
+}
+
+impl<W: Write> Server<W> {
+    fn new(out: W) -> Self {
+        Server {
+            out,
+            resolver: Resolver::default(),
+            documents: HashMap::new(),
+            shutdown: false,
+        }
+    }
+
+    /// Handles a single incoming message, returning an exit code if the
+    /// server should stop.
+    fn handle(&mut self, msg: Value) -> Result<Option<i32>, Error> {
+        let method = msg["method"].as_str().unwrap_or_default();
+        let params = &msg["params"];
+        let id = msg.get("id").cloned();
+
+        let Some(id) = id else {
+            return self.handle_notification(method, params);
+        };
+
+        if self.shutdown && method != "exit" {
+            return self
+                .respond_error(id, INVALID_REQUEST, "server is shutting down")
+                .map(|_| None);
+        }
+
+        let result = match method {
+            "initialize" => json!({
+                "capabilities": {
+                    "textDocumentSync": {
+                        "openClose": true,
+                        "change": TEXT_DOCUMENT_SYNC_FULL,
+                        "save": true,
+                    },
+                    "documentFormattingProvider": true,
+                    // Only SQL can be formatted in fragments.
+                    "documentRangeFormattingProvider": cfg!(feature = "sql"),
+                },
+                "serverInfo": { "name": "metafmt", "version": VERSION },
+            }),
+            "shutdown" => {
+                self.shutdown = true;
+                Value::Null
+            }
+            "textDocument/formatting" => self.formatting(params),
+            "textDocument/rangeFormatting" => self.range_formatting(params),
+            _ => {
+                let msg = format!("method not found: {method}");
+                return self.respond_error(id, METHOD_NOT_FOUND, &msg).map(|_| None);
+            }
+        };
+        self.send(json!({ "jsonrpc": "2.0", "id": id, "result": result }))?;
+        Ok(None)
+    }
+
+    fn handle_notification(&mut self, method: &str, params: &Value) -> Result<Option<i32>, Error> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        match method {
+            "exit" => return Ok(Some(i32::from(!self.shutdown))),
+            "textDocument/didOpen" => {
+                let doc = &params["textDocument"];
+                let path = uri_to_path(uri);
+                let formatters = match &path {
+                    Some(path) => self.resolver.resolve(path),
+                    None => self.resolver.resolve_dir(Path::new(".")),
+                };
+                let document = Document {
+                    path,
+                    language_id: doc["languageId"].as_str().unwrap_or_default().to_string(),
+                    text: doc["text"].as_str().unwrap_or_default().to_string(),
+                    formatters,
+                };
+                self.documents.insert(uri.to_string(), document);
+                self.publish_diagnostics(uri)?;
+            }
+            "textDocument/didChange" => {
+                // Only full document synchronization is advertised, so the
+                // last change always holds the entire document.
+                let changes = params["contentChanges"].as_array();
+                let text = changes
+                    .and_then(|c| c.last())
+                    .and_then(|c| c["text"].as_str());
+                if let (Some(doc), Some(text)) = (self.documents.get_mut(uri), text) {
+                    doc.text = text.to_string();
+                    self.publish_diagnostics(uri)?;
+                }
+            }
+            "textDocument/didSave" => {
+                // Configuration files may have changed, so resolve the
+                // configuration for every open document again.
+                let is_config = uri_to_path(uri)
+                    .is_some_and(|path| path.file_name() == Some(config::FILENAME.as_ref()));
+                if is_config {
+                    self.resolver = Resolver::default();
+                    let uris: Vec<String> = self.documents.keys().cloned().collect();
+                    for uri in uris {
+                        let doc = self.documents.get_mut(&uri).unwrap();
+                        doc.formatters = match &doc.path {
+                            Some(path) => self.resolver.resolve(path),
+                            None => self.resolver.resolve_dir(Path::new(".")),
+                        };
+                        self.publish_diagnostics(&uri)?;
+                    }
+                }
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(uri);
+                self.send_diagnostics(uri, Vec::new())?;
+            }
+            _ => {}
+        }
+        Ok(None)
+    }
+
+    fn formatting(&self, params: &Value) -> Value {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        let Some(doc) = self.documents.get(uri) else {
+            return Value::Null;
+        };
+        let Ok(Some(formatter)) = doc.formatter() else {
+            return Value::Null;
+        };
+        match doc.format(formatter, &doc.text) {
+            Ok(out) if out != doc.text => json!([{
+                "range": {
+                    "start": { "line": 0, "character": 0 },
+                    "end": end_position(&doc.text),
+                },
+                "newText": out,
+            }]),
+            _ => Value::Null,
+        }
+    }
+
+    fn range_formatting(&self, params: &Value) -> Value {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        let Some(doc) = self.documents.get(uri) else {
+            return Value::Null;
+        };
+        let Ok(Some(formatter)) = doc.formatter() else {
+            return Value::Null;
+        };
+        if !formatter.supports_range() {
+            return Value::Null;
+        }
+
+        // Expand the range to cover whole lines.
+        let range = &params["range"];
+        let start_line = range["start"]["line"].as_u64().unwrap_or(0) as usize;
+        let mut end_line = range["end"]["line"].as_u64().unwrap_or(0) as usize;
+        if range["end"]["character"].as_u64().unwrap_or(0) > 0 {
+            end_line += 1;
+        }
+        let start = line_offset(&doc.text, start_line);
+        let end = line_offset(&doc.text, end_line);
+        let fragment = &doc.text[start..end];
+        if fragment.trim().is_empty() {
+            return Value::Null;
+        }
+
+        let mut out = match doc.format(formatter, fragment) {
+            Ok(out) => out,
+            Err(_) => return Value::Null,
+        };
+        if !fragment.ends_with('\n') {
+            out.truncate(out.trim_end_matches('\n').len());
+        }
+        if out == fragment {
+            return Value::Null;
+        }
+        json!([{
+            "range": {
+                "start": position_at(&doc.text, start),
+                "end": position_at(&doc.text, end),
+            },
+            "newText": out,
+        }])
+    }
+
+    fn publish_diagnostics(&mut self, uri: &str) -> Result<(), Error> {
+        let Some(doc) = self.documents.get(uri) else {
+            return Ok(());
+        };
+        let result = match doc.formatter() {
+            Ok(Some(formatter)) => formatter
+                .format_with_warnings(doc.path.as_deref(), &doc.text)
+                .map(|(_, warnings)| warnings),
+            Ok(None) => Ok(Vec::new()),
+            Err(err) => Err(err),
+        };
+        let diagnostic = |message: &str, severity| {
+            let pos = error_position(&doc.text, message);
+            json!({
+                "range": { "start": pos, "end": pos },
+                "severity": severity,
+                "source": "metafmt",
+                "message": message,
+            })
+        };
+        let diagnostics = match result {
+            Ok(warnings) => warnings
+                .iter()
+                .map(|warning| diagnostic(warning, SEVERITY_WARNING))
+                .collect(),
+            Err(err) => vec![diagnostic(&err, SEVERITY_ERROR)],
+        };
+        self.send_diagnostics(uri, diagnostics)
+    }
+
+    fn send_diagnostics(&mut self, uri: &str, diagnostics: Vec<Value>) -> Result<(), Error> {
+        self.send(json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": { "uri": uri, "diagnostics": diagnostics },
+        }))
+    }
+
+    fn respond_error(&mut self, id: Value, code: i32, message: &str) -> Result<(), Error> {
+        self.send(json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": code, "message": message },
+        }))
+    }
+
+    fn send(&mut self, msg: Value) -> Result<(), Error> {
+        let body = serde_json::to_string(&msg)?;
+        write!(self.out, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+        Ok(self.out.flush()?)
+    }
+}
+
+/// Reads a single message from the reader, returning None on EOF.
+fn read_message(r: &mut impl BufRead) -> Result<Option<Value>, Error> {
+    let mut length = None;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if r.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                length = Some(value.trim().parse::<usize>()?);
+            }
+        }
+    }
+
+    let length = length.ok_or("missing Content-Length header")?;
+    let mut body = vec![0; length];
+    r.read_exact(&mut body)?;
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+/// Converts a "file://" URI into a path, decoding any percent-encoded bytes.
+fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let path = uri.strip_prefix("file://")?;
+    let path = path.split(['?', '#']).next().unwrap_or_default();
+
+    let mut bytes = Vec::with_capacity(path.len());
+    let mut iter = path.bytes();
+    while let Some(b) = iter.next() {
+        if b == b'%' {
+            let hex = [iter.next()?, iter.next()?];
+            let hex = std::str::from_utf8(&hex).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+        } else {
+            bytes.push(b);
+        }
+    }
+    let path = String::from_utf8(bytes).ok()?;
+
+    // Windows paths are of the form "file:///C:/path".
+    if path.as_bytes().get(2) == Some(&b':') {
+        return Some(PathBuf::from(&path[1..]));
+    }
+    Some(PathBuf::from(path))
+}
+
+/// Returns the byte offset of the start of the provided line, or the length
+/// of the text if the line is past the end.
+fn line_offset(text: &str, line: usize) -> usize {
+    if line == 0 {
+        return 0;
+    }
+    text.match_indices('\n')
+        .nth(line - 1)
+        .map(|(i, _)| i + 1)
+        .unwrap_or(text.len())
+}
+
+/// Converts a byte offset into an LSP position, which counts characters in
+/// UTF-16 code units.
+fn position_at(text: &str, offset: usize) -> Value {
+    let mut offset = offset.min(text.len());
+    while !text.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    let before = &text[..offset];
+    let line = before.matches('\n').count();
+    let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let character: usize = before[line_start..].chars().map(char::len_utf16).sum();
+    json!({ "line": line, "character": character })
+}
+
+fn end_position(text: &str) -> Value {
+    position_at(text, text.len())
+}
+
+/// Extracts the location of an error from a formatter's error message.
+///
+/// Formatters report errors as strings, using either "line X column Y"
+/// (1-based) or "index X" (a byte offset). The start of the document is
+/// used if no location can be found.
+fn error_position(text: &str, err: &str) -> Value {
+    // Finds the first number that follows the whole word, so that e.g.
+    // "inline" or "pipeline" aren't mistaken for "line".
+    let number_after = |word: &str| {
+        err.match_indices(word).find_map(|(idx, _)| {
+            let before = err[..idx].chars().next_back();
+            if before.is_some_and(|c| c.is_alphanumeric() || c == '_') {
+                return None;
+            }
+            let rest = &err[idx + word.len()..];
+            let digits: String = rest
+                .strip_prefix([' ', ':'])?
+                .trim_start_matches([' ', ':'])
+                .chars()
+                .take_while(char::is_ascii_digit)
+                .collect();
+            digits.parse::<usize>().ok()
+        })
+    };
+
+    if let Some(line) = number_after("line") {
+        let line = line.saturating_sub(1);
+        let column = number_after("column").unwrap_or(1).saturating_sub(1);
+        let start = line_offset(text, line);
+        let end = line_offset(text, line + 1);
+        let col_offset = text[start..end]
+            .char_indices()
+            .nth(column)
+            .map(|(i, _)| start + i)
+            .unwrap_or(start);
+        return position_at(text, col_offset);
+    }
+    if let Some(offset) = number_after("index range").or_else(|| number_after("index")) {
+        return position_at(text, offset);
+    }
+    position_at(text, 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(msg: Value) -> String {
+        let body = msg.to_string();
+        format!("Content-Length: {}\r\n\r\n{body}", body.len())
+    }
+
+    #[cfg(any(feature = "toml", feature = "yaml"))]
+    fn responses(out: &[u8]) -> Vec<Value> {
+        let mut r = out;
+        let mut msgs = Vec::new();
+        while let Some(msg) = read_message(&mut r).unwrap() {
+            msgs.push(msg);
+        }
+        msgs
+    }
+
+    #[test]
+    fn test_read_message() {
+        let input = frame(json!({ "jsonrpc": "2.0", "method": "initialized" }));
+        let msg = read_message(&mut input.as_bytes()).unwrap().unwrap();
+        assert_eq!(msg["method"], "initialized");
+    }
+
+    #[test]
+    #[cfg(feature = "yaml")]
+    fn test_formatting() {
+        let mut server = Server::new(Vec::new());
+        let uri = "file:///tmp/test.yaml";
+        server
+            .handle(json!({
+                "jsonrpc": "2.0",
+                "method": "textDocument/didOpen",
+                "params": { "textDocument": {
+                    "uri": uri, "languageId": "yaml", "version": 1, "text": "a:\n    b: c\n",
+                }},
+            }))
+            .unwrap();
+        server
+            .handle(json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "textDocument/formatting",
+                "params": { "textDocument": { "uri": uri }, "options": {} },
+            }))
+            .unwrap();
+
+        let msgs = responses(&server.out);
+        assert_eq!(msgs[0]["params"]["diagnostics"], json!([]));
+        assert_eq!(msgs[1]["result"][0]["newText"], "a:\n  b: c\n");
+        assert_eq!(
+            msgs[1]["result"][0]["range"]["end"],
+            json!({ "line": 2, "character": 0 })
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "toml")]
+    fn test_diagnostics() {
+        let mut server = Server::new(Vec::new());
+        server
+            .handle(json!({
+                "jsonrpc": "2.0",
+                "method": "textDocument/didOpen",
+                "params": { "textDocument": {
+                    "uri": "file:///tmp/test.toml", "languageId": "toml", "version": 1,
+                    "text": "a = 1\nb = \n",
+                }},
+            }))
+            .unwrap();
+
+        let msgs = responses(&server.out);
+        let diagnostic = &msgs[0]["params"]["diagnostics"][0];
+        assert_eq!(diagnostic["range"]["start"]["line"], 1);
+        assert_eq!(diagnostic["severity"], SEVERITY_ERROR);
+    }
+
+    #[test]
+    fn test_shutdown_exit() {
+        let mut server = Server::new(Vec::new());
+        let shutdown = json!({ "jsonrpc": "2.0", "id": 1, "method": "shutdown" });
+        assert_eq!(server.handle(shutdown).unwrap(), None);
+        let exit = json!({ "jsonrpc": "2.0", "method": "exit" });
+        assert_eq!(server.handle(exit).unwrap(), Some(0));
+    }
+
+    #[test]
+    fn test_error_position() {
+        let text = "a\nbc\ndef\n";
+        assert_eq!(
+            error_position(text, "expected value at line 3 column 2"),
+            json!({ "line": 2, "character": 1 })
+        );
+        assert_eq!(
+            error_position(text, "inline table in pipeline 9: line: 2"),
+            json!({ "line": 1, "character": 0 })
+        );
+        assert_eq!(
+            error_position(text, "unexpected token at index 3"),
+            json!({ "line": 1, "character": 1 })
+        );
+        assert_eq!(
+            error_position(text, "lines 2 to 3 are invalid"),
+            json!({ "line": 0, "character": 0 })
+        );
+    }
+
+    #[test]
+    fn test_uri_to_path() {
+        assert_eq!(
+            uri_to_path("file:///tmp/a%20b/c.yaml"),
+            Some(PathBuf::from("/tmp/a b/c.yaml"))
+        );
+        assert_eq!(uri_to_path("untitled:Untitled-1"), None);
+    }
+
+    #[test]
+    fn test_position_at_utf16() {
+        let text = "a: \"é😀\"\nb";
+        assert_eq!(
+            position_at(text, text.len()),
+            json!({ "line": 1, "character": 1 })
+        );
+        let offset = text.find('"').unwrap() + 1 + "é😀".len();
+        assert_eq!(
+            position_at(text, offset),
+            json!({ "line": 0, "character": 7 })
+        );
+    }
+}
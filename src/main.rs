@@ -1,16 +1,33 @@
-mod fmt;
+mod lsp;
 mod stdin;
-mod types;
+#[cfg(feature = "update")]
 mod update;
 
-use clap::Parser;
+#[cfg(not(feature = "update"))]
+mod update {
+    /// Stands in for the self-updater when the "update" feature is disabled.
+    pub(crate) fn update() -> i32 {
+        eprintln!(
+            "error: self-update support is not compiled in (enable the 'update' cargo feature)"
+        );
+        1
+    }
+}
+
+use clap::{Parser, Subcommand};
+#[cfg(feature = "markdown")]
+use metafmt::types::markdown::ProseWrap;
+use metafmt::{config::Overrides, fmt};
 
 #[global_allocator]
 static GLOBAL: mimalloc::MiMalloc = mimalloc::MiMalloc;
 
 #[derive(Parser)]
-#[command(version, about)]
+#[command(version, about, args_conflicts_with_subcommands = true)]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// A file or directory to format.
     #[clap(default_value = "./")]
     path: String,
@@ -43,6 +60,16 @@ struct Cli {
     #[clap(long)]
     stdin_filetype: Option<String>,
 
+    /// Override how Markdown paragraphs are wrapped.
+    #[cfg(feature = "markdown")]
+    #[clap(long, value_name = "always|never|preserve")]
+    prose_wrap: Option<ProseWrap>,
+
+    /// Override the width Markdown paragraphs are wrapped at.
+    #[cfg(feature = "markdown")]
+    #[clap(long, value_name = "WIDTH")]
+    prose_width: Option<usize>,
+
     /// Do not print info to stderr.
     #[clap(short, long, default_missing_value = "true")]
     quiet: bool,
@@ -56,13 +83,27 @@ struct Cli {
     write: bool,
 }
 
+#[derive(Subcommand)]
+enum Command {
+    /// Run a Language Server Protocol server over stdio.
+    Lsp,
+}
+
 fn main() {
     let cli = Cli::parse();
+    let overrides = Overrides {
+        #[cfg(feature = "markdown")]
+        prose_wrap: cli.prose_wrap,
+        #[cfg(feature = "markdown")]
+        prose_width: cli.prose_width,
+    };
 
-    let exit_code = if cli.update {
+    let exit_code = if let Some(Command::Lsp) = cli.command {
+        lsp::run()
+    } else if cli.update {
         update::update()
     } else if cli.path == "-" {
-        stdin::format(cli.stdin_filetype)
+        stdin::format(cli.stdin_filetype, overrides)
     } else {
         fmt::format(
             cli.path,
@@ -75,6 +116,7 @@ fn main() {
                 no_ignore: cli.no_ignore,
                 quiet: cli.quiet,
                 write: cli.write,
+                overrides,
             },
         )
     };
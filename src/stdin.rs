@@ -1,40 +1,65 @@
-use std::io::{self, Read, Write};
+use std::{
+    io::{self, BufWriter, Read, Write},
+    path::Path,
+};
 
-use crate::types::{json::Json, markdown::Markdown, sql::Sql, toml::Toml, yaml::Yaml, Format};
+use metafmt::{
+    config::{Overrides, Resolver},
+    types::{path_for_name, Format},
+};
 
-pub(crate) fn format(filetype: Option<String>) -> i32 {
+pub(crate) fn format(filetype: Option<String>, overrides: Overrides) -> i32 {
     let Some(filetype) = filetype else {
         eprintln!("error: the '--stdin-filetype' flag must be provided");
         return 1;
     };
 
+    let formatters = match Resolver::with_overrides(overrides).resolve_dir(Path::new(".")) {
+        Ok(formatters) => formatters,
+        Err(err) => {
+            eprintln!("error: {err}");
+            return 1;
+        }
+    };
+    let Some(formatter) = formatters.for_name(&filetype) else {
+        eprintln!("error: unknown format '{filetype}'");
+        return 1;
+    };
+    if formatter.supports_streaming() {
+        return format_stream(formatter);
+    }
+
     let mut input = String::new();
     if let Err(err) = io::stdin().read_to_string(&mut input) {
         eprintln!("error: {err}");
         return 1;
     }
+    format_file(&path_for_name(&filetype), &input, formatter)
+}
 
-    match filetype.as_str() {
-        "json" => format_file(&input, Json::default()),
-        "md" | "markdown" => format_file(&input, Markdown::default()),
-        "sql" => format_file(&input, Sql::default()),
-        "toml" => format_file(&input, Toml::default()),
-        "yaml" | "yml" => format_file(&input, Yaml::default()),
-        format => {
-            eprintln!("error: unknown format '{format}'");
-            1
-        }
+fn format_stream(formatter: &dyn Format) -> i32 {
+    let mut out = BufWriter::new(io::stdout().lock());
+    let result = formatter
+        .format_stream(&mut io::stdin().lock(), &mut out)
+        .and_then(|_| out.flush().map_err(|err| err.to_string()));
+    if let Err(err) = result {
+        eprintln!("error: {err}");
+        return 1;
     }
+    0
 }
 
-fn format_file(input: &str, formatter: impl Format) -> i32 {
-    let output = match formatter.format(input) {
-        Ok(output) => output,
+fn format_file(path: &Path, input: &str, formatter: &dyn Format) -> i32 {
+    let (output, warnings) = match formatter.format_with_warnings(Some(path), input) {
+        Ok(result) => result,
         Err(err) => {
             eprintln!("error: {err}");
             return 1;
         }
     };
+    for warning in warnings {
+        eprintln!("warn: {warning}");
+    }
 
     if let Err(err) = io::stdout().write_all(output.as_bytes()) {
         eprintln!("error: {err}");
@@ -0,0 +1,85 @@
+//! Detection of keys that appear more than once in the same object or
+//! mapping. Parsers keep only the last value for such keys, so the earlier
+//! ones are silently ignored.
+
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+/// DuplicateKeys controls how keys that appear more than once in the same
+/// object are reported.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DuplicateKeys {
+    /// Duplicate keys are errors, and the file is not formatted.
+    #[default]
+    Error,
+    /// Duplicate keys are reported as warnings, and the file is formatted.
+    Warn,
+}
+
+/// Checker tracks the keys of the objects that are open at the current
+/// position in a document, collecting a message for every duplicate.
+#[derive(Default)]
+pub(super) struct Checker {
+    /// The keys of each open object, or None for arrays.
+    stack: Vec<Option<HashMap<String, (usize, usize)>>>,
+    duplicates: Vec<String>,
+}
+
+impl Checker {
+    /// Starts an object if `keyed` is true, or an array otherwise.
+    pub(super) fn open(&mut self, keyed: bool) {
+        self.stack.push(keyed.then(HashMap::new));
+    }
+
+    pub(super) fn close(&mut self) {
+        self.stack.pop();
+    }
+
+    /// Records a key of the innermost object at the provided line and column.
+    pub(super) fn key(&mut self, key: &str, line: usize, column: usize) {
+        let Some(Some(keys)) = self.stack.last_mut() else {
+            return;
+        };
+        match keys.get(key) {
+            Some((first_line, first_column)) => self.duplicates.push(format!(
+                "duplicate key \"{key}\" at line {line} column {column}, \
+                 first defined at line {first_line} column {first_column}"
+            )),
+            None => {
+                keys.insert(key.to_string(), (line, column));
+            }
+        }
+    }
+
+    /// Returns a message for every duplicate key, in the order they appear.
+    pub(super) fn finish(self) -> Vec<String> {
+        self.duplicates
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checker() {
+        let mut checker = Checker::default();
+        checker.open(true);
+        checker.key("a", 1, 2);
+        checker.open(false);
+        checker.key("a", 2, 3);
+        checker.open(true);
+        checker.key("a", 3, 4);
+        checker.close();
+        checker.close();
+        checker.key("b", 4, 2);
+        checker.key("a", 5, 2);
+        checker.close();
+        assert_eq!(
+            checker.finish(),
+            ["duplicate key \"a\" at line 5 column 2, first defined at line 1 column 2"]
+        );
+    }
+}
@@ -0,0 +1,198 @@
+use std::{
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use serde::Deserialize;
+
+use super::Format;
+
+const DEFAULT_TIMEOUT_SECS: u64 = 30;
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// External formats files by running a configured command, which receives the
+/// file's contents on stdin and writes the formatted result to stdout.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct External {
+    /// The file extensions (without the leading dot) handled by the command.
+    pub extensions: Vec<String>,
+    /// The program to run.
+    pub command: String,
+    /// The arguments passed to the program.
+    pub args: Vec<String>,
+    /// The number of seconds to wait before killing the program.
+    pub timeout: Option<u64>,
+    /// The directory of the configuration file that defines the formatter,
+    /// which a relative `command` is resolved against.
+    #[serde(skip)]
+    pub(crate) dir: Option<PathBuf>,
+}
+
+impl External {
+    pub fn handles(&self, ext: &str) -> bool {
+        self.extensions.iter().any(|v| v == ext)
+    }
+
+    /// Sets the directory that a relative `command` is resolved against.
+    pub(crate) fn set_dir(&mut self, dir: &Path) {
+        self.dir = Some(dir.to_path_buf());
+    }
+
+    /// Returns the program to run. A command containing a path separator is
+    /// resolved against the directory of its configuration file, while a bare
+    /// name is looked up in `PATH`.
+    fn program(&self) -> PathBuf {
+        let command = Path::new(&self.command);
+        match &self.dir {
+            Some(dir) if command.is_relative() && command.components().count() > 1 => {
+                dir.join(command)
+            }
+            _ => command.to_path_buf(),
+        }
+    }
+
+    fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout.unwrap_or(DEFAULT_TIMEOUT_SECS))
+    }
+}
+
+impl Format for External {
+    fn format(&self, input: &str) -> Result<String, String> {
+        let cmd = &self.command;
+        let mut child = Command::new(self.program())
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|err| format!("running command '{cmd}': {err}"))?;
+
+        // Write stdin and drain stdout/stderr on separate threads so that a
+        // command producing a lot of output cannot deadlock.
+        let mut stdin = child.stdin.take().unwrap();
+        let mut stdout = child.stdout.take().unwrap();
+        let mut stderr = child.stderr.take().unwrap();
+        let input = input.to_string();
+        let writer = thread::spawn(move || stdin.write_all(input.as_bytes()));
+        let out_reader = thread::spawn(move || {
+            let mut buf = Vec::new();
+            stdout.read_to_end(&mut buf).map(|_| buf)
+        });
+        let err_reader = thread::spawn(move || {
+            let mut buf = String::new();
+            _ = stderr.read_to_string(&mut buf);
+            buf
+        });
+
+        let deadline = Instant::now() + self.timeout();
+        let status = loop {
+            match child.try_wait() {
+                Ok(Some(status)) => break status,
+                Ok(None) if Instant::now() >= deadline => {
+                    _ = child.kill();
+                    _ = child.wait();
+                    // Killing the command closes its ends of the pipes, so
+                    // the threads finish and release the handles they own.
+                    _ = writer.join();
+                    _ = out_reader.join();
+                    _ = err_reader.join();
+                    let secs = self.timeout().as_secs();
+                    return Err(format!("command '{cmd}' timed out after {secs}s"));
+                }
+                Ok(None) => thread::sleep(POLL_INTERVAL),
+                Err(err) => return Err(format!("waiting for command '{cmd}': {err}")),
+            }
+        };
+
+        // A command may exit without reading all of its input; only report
+        // write errors if the command was otherwise successful.
+        let write_result = writer.join().unwrap();
+        let stdout = out_reader.join().unwrap();
+        let stderr = err_reader.join().unwrap();
+
+        if !status.success() {
+            let stderr = stderr.trim();
+            return Err(if stderr.is_empty() {
+                format!("command '{cmd}' failed with {status}")
+            } else {
+                format!("command '{cmd}' failed with {status}: {stderr}")
+            });
+        }
+        write_result.map_err(|err| format!("writing to command '{cmd}': {err}"))?;
+        let stdout = stdout.map_err(|err| format!("reading from command '{cmd}': {err}"))?;
+        String::from_utf8(stdout).map_err(|_| format!("command '{cmd}' produced invalid UTF-8"))
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::{fs, os::unix::fs::PermissionsExt};
+
+    use super::*;
+
+    fn sh(script: &str, timeout: Option<u64>) -> External {
+        External {
+            extensions: vec!["txt".to_string()],
+            command: "sh".to_string(),
+            args: vec!["-c".to_string(), script.to_string()],
+            timeout,
+            ..External::default()
+        }
+    }
+
+    #[test]
+    fn test_external_format() {
+        let ext = sh("tr a-z A-Z", None);
+        assert_eq!(ext.format("hello\n").unwrap(), "HELLO\n");
+    }
+
+    #[test]
+    fn test_external_failure() {
+        let ext = sh("echo 'bad input' >&2; exit 3", None);
+        let err = ext.format("hello\n").unwrap_err();
+        assert!(err.contains("bad input"), "{err}");
+    }
+
+    #[test]
+    fn test_external_timeout() {
+        let ext = sh("exec sleep 5", Some(0));
+        let err = ext.format("").unwrap_err();
+        assert_eq!(err, "command 'sh' timed out after 0s");
+    }
+
+    #[test]
+    fn test_external_missing_command() {
+        let ext = External {
+            command: "metafmt-does-not-exist".to_string(),
+            ..External::default()
+        };
+        assert!(ext.format("").is_err());
+    }
+
+    #[test]
+    fn test_external_relative_command() {
+        let dir = std::env::temp_dir().join(format!("metafmt-external-{}", std::process::id()));
+        fs::create_dir_all(dir.join("bin")).unwrap();
+        let script = dir.join("bin").join("upper");
+        fs::write(&script, "#!/bin/sh\ntr a-z A-Z\n").unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let mut ext = External {
+            command: "bin/upper".to_string(),
+            ..External::default()
+        };
+        assert!(ext.format("hello\n").is_err());
+        ext.set_dir(&dir);
+        assert_eq!(ext.format("hello\n").unwrap(), "HELLO\n");
+
+        let mut ext = sh("tr a-z A-Z", None);
+        ext.set_dir(&dir);
+        assert_eq!(ext.format("hello\n").unwrap(), "HELLO\n");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,543 @@
+use std::{fmt::Write, path::Path};
+
+use serde::Deserialize;
+
+use super::{Format, Indent};
+
+/// Ini formats INI-style configuration files, such as setup.cfg, tox.ini,
+/// .editorconfig, .gitconfig and Java .properties files.
+#[derive(Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Ini {
+    /// The dialect that determines how sections, comments and multi-line
+    /// values are read.
+    pub dialect: Dialect,
+    /// The indentation of the continuation lines of multi-line values.
+    pub indent: Indent,
+    /// Writes non-ASCII characters in keys and values of .properties files as
+    /// `\uXXXX` escapes, for files that are read as ISO-8859-1.
+    pub escape_unicode: bool,
+}
+
+impl Default for Ini {
+    fn default() -> Self {
+        Ini {
+            dialect: Dialect::default(),
+            indent: Indent::Spaces(4),
+            escape_unicode: false,
+        }
+    }
+}
+
+/// Dialect selects the INI syntax that is recognized.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Dialect {
+    /// Properties is used for .properties files, Git for .gitconfig and
+    /// .gitmodules files, and Configparser for everything else.
+    #[default]
+    Auto,
+    /// Python's configparser: values continue on lines that are indented
+    /// further than their key, and comments start with `#` or `;`.
+    Configparser,
+    /// git-config: keys are indented with a tab within their section, and
+    /// values continue after a trailing backslash.
+    Git,
+    /// Java properties: there are no sections, keys may contain escaped
+    /// delimiters, comments start with `#` or `!`, and values continue after
+    /// a trailing backslash.
+    Properties,
+}
+
+impl Dialect {
+    fn for_path(path: &Path) -> Self {
+        let name = path
+            .file_name()
+            .and_then(std::ffi::OsStr::to_str)
+            .unwrap_or_default();
+        let ext = path
+            .extension()
+            .and_then(std::ffi::OsStr::to_str)
+            .unwrap_or_default();
+        match (name, ext) {
+            (_, "properties") => Dialect::Properties,
+            (".gitconfig" | ".gitmodules", _) | (_, "gitconfig") => Dialect::Git,
+            _ => Dialect::Configparser,
+        }
+    }
+}
+
+impl Format for Ini {
+    fn format(&self, input: &str) -> Result<String, String> {
+        let dialect = match self.dialect {
+            Dialect::Auto => Dialect::Configparser,
+            dialect => dialect,
+        };
+        self.format_ini(input, dialect)
+    }
+
+    fn format_file(&self, path: &Path, input: &str) -> Result<String, String> {
+        let dialect = match self.dialect {
+            Dialect::Auto => Dialect::for_path(path),
+            dialect => dialect,
+        };
+        self.format_ini(input, dialect)
+    }
+}
+
+impl Ini {
+    fn format_ini(&self, input: &str, dialect: Dialect) -> Result<String, String> {
+        let input = input.replace("\r\n", "\n");
+        let (bom, input) = match input.strip_prefix('\u{feff}') {
+            Some(rest) => ("\u{feff}", rest),
+            None => ("", input.as_str()),
+        };
+
+        let mut lines = match dialect {
+            Dialect::Git => parse_git(input)?,
+            Dialect::Properties => parse_properties(input),
+            _ => parse_configparser(input)?,
+        };
+        if dialect == Dialect::Properties && self.escape_unicode {
+            for line in &mut lines {
+                if let Line::Entry(entry) = line {
+                    entry.key = escape_unicode(&entry.key);
+                    entry.value = escape_unicode(&entry.value);
+                    for text in &mut entry.continuation {
+                        *text = escape_unicode(text);
+                    }
+                }
+            }
+        }
+
+        let mut out = bom.to_string();
+        write_lines(&mut out, &lines, dialect, &self.indent.to_str());
+        if out == bom {
+            return Ok(String::new());
+        }
+        Ok(out)
+    }
+}
+
+/// Line is a logical line of a file, with its surrounding whitespace removed.
+enum Line {
+    Blank,
+    Comment(String),
+    Section(String),
+    Entry(Entry),
+}
+
+/// Entry is a key with an optional value, which may continue on the
+/// following lines.
+struct Entry {
+    key: String,
+    delimiter: Option<char>,
+    value: String,
+    /// The continuation lines of the value. Blank lines are empty, and
+    /// git-config lines are kept as written.
+    continuation: Vec<String>,
+}
+
+fn is_comment(text: &str, prefixes: &[char]) -> bool {
+    text.starts_with(prefixes)
+}
+
+/// Returns true if the line ends with an unescaped backslash, which
+/// continues it on the next line.
+fn is_continued(line: &str) -> bool {
+    let trimmed = line.trim_end_matches('\\');
+    (line.len() - trimmed.len()) % 2 == 1
+}
+
+fn invalid_header(i: usize) -> String {
+    format!("invalid section header at line {} column 1", i + 1)
+}
+
+/// Splits a line at its first `=` or `:`, the way configparser reads options.
+fn split_entry(text: &str, delimiters: &[char]) -> Entry {
+    match text.find(delimiters) {
+        Some(i) => Entry {
+            key: text[..i].trim_end().to_string(),
+            delimiter: text[i..].chars().next(),
+            value: text[i + 1..].trim().to_string(),
+            continuation: Vec::new(),
+        },
+        None => Entry {
+            key: text.to_string(),
+            delimiter: None,
+            value: String::new(),
+            continuation: Vec::new(),
+        },
+    }
+}
+
+fn parse_configparser(input: &str) -> Result<Vec<Line>, String> {
+    let mut lines = Vec::new();
+    // Blank lines and comments are only part of a multi-line value if it
+    // continues after them.
+    let mut pending = Vec::new();
+    // The indentation of the current key, if its value can continue.
+    let mut key_indent = None;
+    for (i, raw) in input.lines().enumerate() {
+        let text = raw.trim();
+        if text.is_empty() {
+            pending.push(Line::Blank);
+            continue;
+        }
+        if is_comment(text, &['#', ';']) {
+            pending.push(Line::Comment(text.to_string()));
+            continue;
+        }
+
+        let indent = raw.len() - raw.trim_start().len();
+        if let (Some(Line::Entry(entry)), Some(key_indent)) = (lines.last_mut(), key_indent) {
+            if indent > key_indent {
+                for line in pending.drain(..) {
+                    entry.continuation.push(match line {
+                        Line::Comment(text) => text,
+                        _ => String::new(),
+                    });
+                }
+                entry.continuation.push(text.to_string());
+                continue;
+            }
+        }
+        lines.append(&mut pending);
+
+        if text.starts_with('[') && text[1..].rfind(']').is_some_and(|i| i > 0) {
+            lines.push(Line::Section(text.to_string()));
+            key_indent = None;
+            continue;
+        }
+        let entry = split_entry(text, &['=', ':']);
+        if entry.delimiter.is_none() && text.starts_with('[') {
+            return Err(invalid_header(i));
+        }
+        key_indent = entry.delimiter.map(|_| indent);
+        lines.push(Line::Entry(entry));
+    }
+    lines.append(&mut pending);
+    Ok(lines)
+}
+
+fn parse_git(input: &str) -> Result<Vec<Line>, String> {
+    let mut lines = Vec::new();
+    let mut continued = false;
+    for (i, raw) in input.lines().enumerate() {
+        if continued {
+            if let Some(Line::Entry(entry)) = lines.last_mut() {
+                entry.continuation.push(raw.to_string());
+            }
+            continued = is_continued(raw);
+            continue;
+        }
+
+        let text = raw.trim();
+        if text.is_empty() {
+            lines.push(Line::Blank);
+        } else if is_comment(text, &['#', ';']) {
+            lines.push(Line::Comment(text.to_string()));
+        } else if text.starts_with('[') {
+            let header = git_section(text).ok_or_else(|| invalid_header(i))?;
+            lines.push(Line::Section(header));
+        } else {
+            let entry = split_entry(text, &['=']);
+            continued = is_continued(&entry.value);
+            lines.push(Line::Entry(entry));
+        }
+    }
+    Ok(lines)
+}
+
+/// Normalizes a git-config section header, such as `[remote  "origin"]`.
+/// Anything after the header, such as a comment, is kept as written.
+fn git_section(text: &str) -> Option<String> {
+    let inner = text.strip_prefix('[')?;
+    let end = inner.find(|c: char| c.is_whitespace() || c == ']' || c == '"')?;
+    let name = &inner[..end];
+    let rest = inner[end..].trim_start();
+    let tail = |rest: &str| match rest.trim() {
+        "" => String::new(),
+        rest => format!(" {rest}"),
+    };
+    if let Some(rest) = rest.strip_prefix(']') {
+        return Some(format!("[{name}]{}", tail(rest)));
+    }
+
+    let quoted = rest.strip_prefix('"')?;
+    let mut escaped = false;
+    let close = quoted.char_indices().find_map(|(i, c)| {
+        match (escaped, c) {
+            (false, '\\') => escaped = true,
+            (false, '"') => return Some(i),
+            _ => escaped = false,
+        }
+        None
+    })?;
+    let rest = quoted[close + 1..].strip_prefix(']')?;
+    Some(format!("[{name} \"{}\"]{}", &quoted[..close], tail(rest)))
+}
+
+fn parse_properties(input: &str) -> Vec<Line> {
+    const WHITESPACE: [char; 3] = [' ', '\t', '\x0c'];
+
+    let mut lines = Vec::new();
+    let mut continued = false;
+    for raw in input.lines() {
+        // Leading whitespace is never part of a key or value, but trailing
+        // whitespace is part of the value.
+        let text = raw.trim_start_matches(WHITESPACE);
+        if continued {
+            if let Some(Line::Entry(entry)) = lines.last_mut() {
+                entry.continuation.push(text.to_string());
+            }
+            continued = is_continued(text);
+            continue;
+        }
+
+        if text.trim_end().is_empty() {
+            lines.push(Line::Blank);
+            continue;
+        }
+        if is_comment(text, &['#', '!']) {
+            lines.push(Line::Comment(text.trim_end().to_string()));
+            continue;
+        }
+
+        // The key ends at the first unescaped delimiter or whitespace.
+        let mut escaped = false;
+        let end = text
+            .char_indices()
+            .find(|&(_, c)| {
+                let end = !escaped && (c == '=' || c == ':' || WHITESPACE.contains(&c));
+                escaped = !escaped && c == '\\';
+                end
+            })
+            .map_or(text.len(), |(i, _)| i);
+        let rest = text[end..].trim_start_matches(WHITESPACE);
+        let (end, delimiter, value) = match rest.strip_prefix(['=', ':']) {
+            Some(value) => (
+                end,
+                rest.chars().next(),
+                value.trim_start_matches(WHITESPACE),
+            ),
+            None if rest.is_empty() => (end, None, rest),
+            // A delimiter may still follow on the next line, so a key that
+            // continues before its value is kept as written.
+            None if rest == "\\" => (text.len(), None, ""),
+            None => (end, Some('='), rest),
+        };
+        continued = is_continued(text);
+        lines.push(Line::Entry(Entry {
+            key: text[..end].to_string(),
+            delimiter,
+            value: value.to_string(),
+            continuation: Vec::new(),
+        }));
+    }
+    lines
+}
+
+/// Writes non-ASCII characters as `\uXXXX` escapes of their UTF-16 code
+/// units, the way java.util.Properties stores them.
+fn escape_unicode(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if c.is_ascii() {
+            out.push(c);
+            continue;
+        }
+        for unit in c.encode_utf16(&mut [0; 2]) {
+            _ = write!(out, "\\u{unit:04X}");
+        }
+    }
+    out
+}
+
+fn write_lines(out: &mut String, lines: &[Line], dialect: Dialect, indent: &str) {
+    let key_indent = match dialect {
+        Dialect::Git => "\t",
+        _ => "",
+    };
+    let start = out.len();
+    let mut blank = false;
+    let mut in_section = false;
+    // Comments directly above a section header belong to that section.
+    let mut header_comments = false;
+    for (i, line) in lines.iter().enumerate() {
+        let starts_section = match line {
+            Line::Blank => {
+                blank = true;
+                continue;
+            }
+            Line::Section(_) => !header_comments,
+            Line::Comment(_) if !matches!(lines.get(i.wrapping_sub(1)), Some(Line::Comment(_))) => {
+                let next = lines[i..].iter().find(|l| !matches!(l, Line::Comment(_)));
+                header_comments = matches!(next, Some(Line::Section(_)));
+                header_comments
+            }
+            _ => false,
+        };
+        if out.len() > start && (blank || starts_section) {
+            out.push('\n');
+        }
+        blank = false;
+
+        match line {
+            Line::Blank => {}
+            Line::Section(header) => {
+                header_comments = false;
+                in_section = true;
+                out.push_str(header);
+            }
+            Line::Comment(text) => {
+                if in_section && !header_comments {
+                    out.push_str(key_indent);
+                }
+                out.push_str(text);
+            }
+            Line::Entry(entry) => {
+                header_comments = false;
+                out.push_str(key_indent);
+                write_entry(out, entry, dialect, indent);
+            }
+        }
+        out.push('\n');
+    }
+}
+
+fn write_entry(out: &mut String, entry: &Entry, dialect: Dialect, indent: &str) {
+    out.push_str(&entry.key);
+    if let Some(delimiter) = entry.delimiter {
+        let spaced = dialect != Dialect::Properties;
+        if spaced && delimiter == '=' {
+            out.push(' ');
+        }
+        out.push(delimiter);
+        if spaced && !entry.value.is_empty() {
+            out.push(' ');
+        }
+        out.push_str(&entry.value);
+    }
+    for text in &entry.continuation {
+        out.push('\n');
+        if dialect == Dialect::Git {
+            out.push_str(text);
+        } else if !text.is_empty() {
+            out.push_str(indent);
+            out.push_str(text);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(dialect: Dialect, input: &str) -> String {
+        let ini = Ini {
+            dialect,
+            ..Ini::default()
+        };
+        let out = ini.format(input).unwrap();
+        assert_eq!(ini.format(&out).unwrap(), out, "not idempotent");
+        out
+    }
+
+    #[test]
+    fn test_configparser() {
+        let input = "\n\n# setup.cfg\n[metadata]\nname=demo\n  version :1.0\n\n\n\n\
+                     [options]\ninstall_requires =\n  requests\n\n      click  \n  # pinned\n  rich\n\
+                     python_requires=>=3.8\n\n; extras\n\n[options.extras_require]\n\
+                     flag\ndev=\n\n";
+        let want = "# setup.cfg\n[metadata]\nname = demo\n    version :1.0\n\n\
+                    [options]\ninstall_requires =\n    requests\n\n    click\n    # pinned\n    rich\n\
+                    python_requires = >=3.8\n\n; extras\n\n[options.extras_require]\n\
+                    flag\ndev =\n";
+        assert_eq!(format(Dialect::Configparser, input), want);
+    }
+
+    #[test]
+    fn test_configparser_sections() {
+        let input = "root=true\n# python\n[*.py]\nindent_size = 4\n  [*.md]\n\
+                     trim_trailing_whitespace=false\n[ spaced ]\nkey :value\n";
+        // An indented line after a key continues its value.
+        let want = "root = true\n\n# python\n[*.py]\nindent_size = 4\n    [*.md]\n\
+                    trim_trailing_whitespace = false\n\n[ spaced ]\nkey: value\n";
+        assert_eq!(format(Dialect::Configparser, input), want);
+    }
+
+    #[test]
+    fn test_git() {
+        let input = "[core]\nbare=false\n  # comment\n    autocrlf = input ; inline\n\
+                     [remote    \"origin\"]\nurl = git@example.com:a/b.git\n\
+                     [alias]\n  lg = log \\\n  --oneline \\\n\t--graph\n\n\n[include] # paths\n\
+                     \tpath = ~/.gitconfig.local\n[user.name]\nsecure\n";
+        let want = "[core]\n\tbare = false\n\t# comment\n\tautocrlf = input ; inline\n\n\
+                    [remote \"origin\"]\n\turl = git@example.com:a/b.git\n\n\
+                    [alias]\n\tlg = log \\\n  --oneline \\\n\t--graph\n\n[include] # paths\n\
+                    \tpath = ~/.gitconfig.local\n\n[user.name]\n\tsecure\n";
+        assert_eq!(format(Dialect::Git, input), want);
+    }
+
+    #[test]
+    fn test_properties() {
+        let input = "# comment \\\nkey1=value\nkey2 : value2\nkey3   value3 \n\
+                     path\\=with\\:escapes\\ here = a\\\n    b\\\\\n    ! not a comment\n\
+                     empty\n\n\n  ! comment\nmulti\\\n  line = x\nnested.key= \\\n\tcontinued\nsplit \\\n  = value\n";
+        let want = "# comment \\\nkey1=value\nkey2:value2\nkey3=value3 \n\
+                    path\\=with\\:escapes\\ here=a\\\n    b\\\\\n! not a comment\n\
+                    empty\n\n! comment\nmulti\\\n    line = x\nnested.key=\\\n    continued\nsplit \\\n    = value\n";
+        assert_eq!(format(Dialect::Properties, input), want);
+    }
+
+    #[test]
+    fn test_escape_unicode() {
+        let ini = Ini {
+            dialect: Dialect::Properties,
+            escape_unicode: true,
+            ..Ini::default()
+        };
+        assert_eq!(
+            ini.format("# café\ngreeting=héllo 👋\n").unwrap(),
+            "# café\ngreeting=h\\u00E9llo \\uD83D\\uDC4B\n"
+        );
+    }
+
+    #[test]
+    fn test_dialect_for_path() {
+        let ini = Ini::default();
+        let input = "[a]\nb=c\n";
+        let cases = [
+            ("setup.cfg", "[a]\nb = c\n"),
+            (".editorconfig", "[a]\nb = c\n"),
+            (".gitconfig", "[a]\n\tb = c\n"),
+            ("repo/.gitmodules", "[a]\n\tb = c\n"),
+            ("messages.properties", "[a]\nb=c\n"),
+        ];
+        for (path, want) in cases {
+            let got = ini.format_file(Path::new(path), input).unwrap();
+            assert_eq!(got, want, "{path}");
+        }
+        assert_eq!(ini.format(input).unwrap(), "[a]\nb = c\n");
+    }
+
+    #[test]
+    fn test_errors() {
+        let ini = Ini::default();
+        assert_eq!(
+            ini.format("[a]\nb = c\n[d\n").unwrap_err(),
+            "invalid section header at line 3 column 1"
+        );
+        let git = Ini {
+            dialect: Dialect::Git,
+            ..Ini::default()
+        };
+        assert_eq!(
+            git.format("[a \"b]\n").unwrap_err(),
+            "invalid section header at line 1 column 1"
+        );
+        assert_eq!(ini.format("\u{feff}\r\n\r\n").unwrap(), "");
+        assert_eq!(ini.format("\u{feff}a=b\r\n").unwrap(), "\u{feff}a = b\n");
+    }
+}
@@ -1,12 +1,743 @@
-use fjson::to_jsonc;
+use std::{collections::HashMap, path::Path};
 
-use super::Format;
+use fjson::{
+    ast::{ArrayValue, Metadata, ObjectValue, Root, Value, ValueToken},
+    format::Options,
+    scanner::{Scanner, Token},
+};
+use serde::Deserialize;
 
-#[derive(Clone, Copy, Default)]
-pub(crate) struct Json {}
+use super::{
+    duplicates::{Checker, DuplicateKeys},
+    Format, Indent, Locator,
+};
+
+/// Json formats JSON, including the JSONC, HJSON and JWCC extensions.
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Json {
+    /// The indentation of nested values.
+    pub indent: Indent,
+    /// The line width that arrays and objects are kept within when they're
+    /// written on a single line.
+    pub line_width: usize,
+    /// The maximum number of values in an array written on a single line.
+    pub array_values_per_line: usize,
+    /// The maximum number of key/value pairs in an object written on a
+    /// single line.
+    pub object_pairs_per_line: usize,
+    /// Ends the last entry of arrays and objects that span multiple lines
+    /// with a comma. Only applies to files that allow them, such as .jsonc.
+    pub trailing_commas: bool,
+    /// Sorts the keys of every object alphabetically.
+    pub sort_keys: bool,
+    /// The order of the top-level keys for files with the provided name, e.g.
+    /// `{"package.json": ["name", "version"]}`. Keys that aren't listed are
+    /// placed after the listed ones.
+    pub key_order: HashMap<String, Vec<String>>,
+    /// Applies the canonical key order and sorting rules for well-known
+    /// files, such as package.json, tsconfig.json and composer.json.
+    pub presets: bool,
+    /// How comments and trailing commas are handled in files with the .json
+    /// extension. Files with the .jsonc, .hjson and .jwcc extensions always
+    /// allow them, as do .json files that are read as JSON with comments,
+    /// such as tsconfig.json.
+    pub strict: Strict,
+    /// Reports keys that appear more than once in the same object.
+    pub duplicate_keys: DuplicateKeys,
+}
+
+impl Default for Json {
+    fn default() -> Self {
+        Json {
+            indent: Indent::default(),
+            line_width: 80,
+            array_values_per_line: 4,
+            object_pairs_per_line: 1,
+            trailing_commas: false,
+            sort_keys: false,
+            key_order: HashMap::new(),
+            presets: false,
+            strict: Strict::default(),
+            duplicate_keys: DuplicateKeys::default(),
+        }
+    }
+}
+
+/// Strict controls how syntax that isn't part of standard JSON is handled.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Strict {
+    /// Report comments and trailing commas as errors.
+    #[default]
+    Error,
+    /// Remove comments and trailing commas.
+    Fix,
+    /// Keep comments, as in .jsonc files.
+    Off,
+}
 
 impl Format for Json {
     fn format(&self, input: &str) -> Result<String, String> {
-        to_jsonc(input).map_err(|err| err.to_string())
+        self.format_json(input, None)
+    }
+
+    fn format_file(&self, path: &Path, input: &str) -> Result<String, String> {
+        self.format_json(input, Some(path))
+    }
+
+    fn warnings(&self, _path: Option<&Path>, input: &str) -> Vec<String> {
+        match self.duplicate_keys {
+            DuplicateKeys::Warn => duplicate_keys(input, 1),
+            DuplicateKeys::Error => Vec::new(),
+        }
+    }
+}
+
+impl Json {
+    fn format_json(&self, input: &str, path: Option<&Path>) -> Result<String, String> {
+        let mut root = fjson::ast::parse(input).map_err(|err| err.to_string())?;
+
+        let extension = path.and_then(Path::extension);
+        let is_json =
+            extension.is_some_and(|ext| ext == "json") && !path.is_some_and(allows_comments);
+        match self.strict {
+            Strict::Error if is_json => check_strict(input)?,
+            // Trailing commas are never written, so only comments are removed.
+            Strict::Fix if is_json => strip_comments(&mut root),
+            _ => {}
+        }
+        if self.duplicate_keys == DuplicateKeys::Error {
+            let duplicates = duplicate_keys(input, 1);
+            if !duplicates.is_empty() {
+                return Err(duplicates.join("\n"));
+            }
+        }
+
+        let file_name = path
+            .and_then(Path::file_name)
+            .and_then(std::ffi::OsStr::to_str);
+        let preset = match self.presets {
+            true => file_name.and_then(Preset::for_file_name),
+            false => None,
+        };
+        if self.sort_keys {
+            sort_all(&mut root.value);
+        }
+        for path in preset.map(|p| p.sort_objects).unwrap_or_default() {
+            if let Some(ValueToken::Object(members)) = get_mut(&mut root.value, path) {
+                sort_members(members, |key| key);
+            }
+        }
+        let order = file_name
+            .and_then(|name| self.key_order.get(name))
+            .map(|keys| keys.iter().map(String::as_str).collect())
+            .or_else(|| preset.map(|p| p.keys.to_vec()));
+        if let (Some(order), ValueToken::Object(members)) = (order, &mut root.value.token) {
+            sort_members(members, |key| {
+                order.iter().position(|v| *v == key).unwrap_or(order.len())
+            });
+        }
+
+        let indent = self.indent.to_str();
+        let opts = Options::default()
+            .with_indent(&indent)
+            .with_line_length(self.line_width)
+            .with_max_array_values_per_line(self.array_values_per_line)
+            .with_max_object_pairs_per_line(self.object_pairs_per_line);
+        let mut out = String::with_capacity(input.len() + 128);
+        fjson::format::write_jsonc_opts(&mut out, &root, &opts).map_err(|err| err.to_string())?;
+
+        // Content that isn't known to come from a file may be read as JSON.
+        if self.trailing_commas && extension.is_some() && !is_json {
+            out = add_trailing_commas(&out);
+        }
+        Ok(out)
+    }
+}
+
+/// Adds a comma after the last entry of every array and object that spans
+/// multiple lines.
+fn add_trailing_commas(input: &str) -> String {
+    let mut offsets = Vec::new();
+    let mut value_end = None;
+    let mut newline = false;
+    for event in Scanner::new(input).flatten() {
+        match event.token {
+            Token::Newline => newline = true,
+            Token::LineComment(_) | Token::BlockComment(_) => {}
+            Token::ObjectEnd | Token::ArrayEnd => {
+                if let Some(end) = value_end.filter(|_| newline) {
+                    offsets.push(end);
+                }
+                value_end = Some(event.range.end);
+                newline = false;
+            }
+            Token::ObjectStart | Token::ArrayStart | Token::Comma | Token::Colon => {
+                value_end = None;
+                newline = false;
+            }
+            _ => {
+                value_end = Some(event.range.end);
+                newline = false;
+            }
+        }
+    }
+
+    let mut out = String::with_capacity(input.len() + offsets.len());
+    let mut start = 0;
+    for offset in offsets {
+        out.push_str(&input[start..offset]);
+        out.push(',');
+        start = offset;
+    }
+    out.push_str(&input[start..]);
+    out
+}
+
+// --- Strict mode ---
+
+/// Returns an error describing every comment and trailing comma in the input,
+/// which must already be known to be valid JSONC.
+fn check_strict(input: &str) -> Result<(), String> {
+    let errors = strict_errors(input);
+    if errors.is_empty() {
+        return Ok(());
+    }
+
+    let mut locator = Locator::new(input, 1);
+    let errors = errors.into_iter().map(|(offset, what)| {
+        let (line, column) = locator.locate(offset);
+        format!("{what} is not allowed in JSON at line {line} column {column}")
+    });
+    Err(errors.collect::<Vec<_>>().join("\n"))
+}
+
+/// Returns the byte offset and a description of every comment and trailing
+/// comma in the input, in order.
+pub(super) fn strict_errors(input: &str) -> Vec<(usize, &'static str)> {
+    let mut errors = Vec::new();
+    let mut last_comma = None;
+    for event in Scanner::new(input).flatten() {
+        match event.token {
+            Token::LineComment(_) | Token::BlockComment(_) => {
+                errors.push((event.range.start, "comment"));
+            }
+            Token::Newline => {}
+            Token::ObjectEnd | Token::ArrayEnd if last_comma.is_some() => {
+                errors.push((last_comma.take().unwrap_or_default(), "trailing comma"));
+            }
+            Token::Comma => last_comma = Some(event.range.start),
+            _ => last_comma = None,
+        }
+    }
+    errors.sort_unstable();
+    errors
+}
+
+/// Returns a message for every key that appears more than once in the same
+/// object, numbering lines from `first_line`.
+pub(super) fn duplicate_keys(input: &str, first_line: usize) -> Vec<String> {
+    let mut checker = Checker::default();
+    // Whether each open container is an object, and whether a key may follow.
+    let mut objects = Vec::new();
+    let mut expect_key = false;
+    let mut locator = Locator::new(input, first_line);
+    for event in Scanner::new(input).flatten() {
+        match event.token {
+            Token::ObjectStart | Token::ArrayStart => {
+                let is_object = event.token == Token::ObjectStart;
+                checker.open(is_object);
+                objects.push(is_object);
+                expect_key = is_object;
+            }
+            Token::ObjectEnd | Token::ArrayEnd => {
+                checker.close();
+                objects.pop();
+                expect_key = false;
+            }
+            Token::Comma => expect_key = objects.last() == Some(&true),
+            Token::String(key) if expect_key => {
+                let (line, column) = locator.locate(event.range.start);
+                checker.key(key, line, column);
+                expect_key = false;
+            }
+            Token::Newline | Token::LineComment(_) | Token::BlockComment(_) => {}
+            _ => expect_key = false,
+        }
+    }
+    checker.finish()
+}
+
+/// Returns true if the .json file is read as JSON with comments by the tools
+/// that use it, such as TypeScript and VS Code.
+fn allows_comments(path: &Path) -> bool {
+    let in_vscode = path
+        .parent()
+        .and_then(Path::file_name)
+        .is_some_and(|dir| dir == ".vscode");
+    let name = path
+        .file_name()
+        .and_then(std::ffi::OsStr::to_str)
+        .unwrap_or_default();
+    in_vscode || name == "jsconfig.json" || name.starts_with("tsconfig.")
+}
+
+/// Removes every comment from the document, keeping blank lines.
+fn strip_comments(root: &mut Root) {
+    let is_newline = |meta: &Metadata| matches!(meta, Metadata::Newline);
+    root.meta_above.clear();
+    root.meta_below.retain(is_newline);
+    root.meta_below.dedup();
+    strip_value_comments(&mut root.value);
+}
+
+fn strip_value_comments(value: &mut Value) {
+    value.comments.clear();
+    match &mut value.token {
+        ValueToken::Object(members) => {
+            members.retain(|member| !matches!(member, ObjectValue::Metadata(Metadata::Comment(_))));
+            // Removing a comment can leave adjacent or leading blank lines.
+            members.dedup_by(|a, b| is_blank(a) && is_blank(b));
+            if members.first().is_some_and(is_blank) {
+                members.remove(0);
+            }
+            for member in members.iter_mut() {
+                if let ObjectValue::KeyVal(_, v) = member {
+                    strip_value_comments(v);
+                }
+            }
+        }
+        ValueToken::Array(values) => {
+            values.retain(|value| !matches!(value, ArrayValue::Metadata(Metadata::Comment(_))));
+            values.dedup_by(|a, b| {
+                let is_blank =
+                    |v: &ArrayValue| matches!(v, ArrayValue::Metadata(Metadata::Newline));
+                is_blank(a) && is_blank(b)
+            });
+            if let Some(ArrayValue::Metadata(Metadata::Newline)) = values.first() {
+                values.remove(0);
+            }
+            for value in values.iter_mut() {
+                if let ArrayValue::ArrayVal(v) = value {
+                    strip_value_comments(v);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn is_blank(member: &ObjectValue) -> bool {
+    matches!(member, ObjectValue::Metadata(Metadata::Newline))
+}
+
+// --- Presets ---
+
+/// Preset describes the canonical layout of a well-known file.
+struct Preset {
+    /// The order of the top-level keys. Unknown keys are placed after these,
+    /// in their original order.
+    keys: &'static [&'static str],
+    /// Top-level objects whose keys are sorted alphabetically.
+    sort_objects: &'static [&'static str],
+}
+
+const PACKAGE_JSON: Preset = Preset {
+    keys: &[
+        "$schema",
+        "name",
+        "displayName",
+        "version",
+        "private",
+        "description",
+        "keywords",
+        "homepage",
+        "bugs",
+        "repository",
+        "funding",
+        "license",
+        "author",
+        "contributors",
+        "type",
+        "exports",
+        "main",
+        "module",
+        "browser",
+        "types",
+        "typings",
+        "bin",
+        "files",
+        "workspaces",
+        "scripts",
+        "config",
+        "dependencies",
+        "devDependencies",
+        "peerDependencies",
+        "peerDependenciesMeta",
+        "optionalDependencies",
+        "bundledDependencies",
+        "overrides",
+        "resolutions",
+        "engines",
+        "packageManager",
+        "os",
+        "cpu",
+        "publishConfig",
+    ],
+    sort_objects: &[
+        "dependencies",
+        "devDependencies",
+        "peerDependencies",
+        "peerDependenciesMeta",
+        "optionalDependencies",
+        "resolutions",
+    ],
+};
+
+const TSCONFIG_JSON: Preset = Preset {
+    keys: &[
+        "$schema",
+        "extends",
+        "compilerOptions",
+        "include",
+        "exclude",
+        "files",
+        "references",
+    ],
+    sort_objects: &[],
+};
+
+const COMPOSER_JSON: Preset = Preset {
+    keys: &[
+        "$schema",
+        "name",
+        "type",
+        "description",
+        "keywords",
+        "homepage",
+        "version",
+        "license",
+        "authors",
+        "support",
+        "funding",
+        "require",
+        "require-dev",
+        "conflict",
+        "replace",
+        "provide",
+        "suggest",
+        "autoload",
+        "autoload-dev",
+        "repositories",
+        "minimum-stability",
+        "prefer-stable",
+        "config",
+        "scripts",
+        "extra",
+        "bin",
+        "archive",
+    ],
+    sort_objects: &["require", "require-dev", "suggest"],
+};
+
+impl Preset {
+    fn for_file_name(name: &str) -> Option<&'static Self> {
+        match name {
+            "package.json" => Some(&PACKAGE_JSON),
+            "composer.json" => Some(&COMPOSER_JSON),
+            "tsconfig.json" | "jsconfig.json" => Some(&TSCONFIG_JSON),
+            // Extended configurations, e.g. tsconfig.build.json.
+            name if name.starts_with("tsconfig.") && name.ends_with(".json") => {
+                Some(&TSCONFIG_JSON)
+            }
+            _ => None,
+        }
+    }
+}
+
+// --- Sorting ---
+
+/// Returns the value of the provided key in an object.
+fn get_mut<'v, 'a>(value: &'v mut Value<'a>, key: &str) -> Option<&'v mut ValueToken<'a>> {
+    let ValueToken::Object(members) = &mut value.token else {
+        return None;
+    };
+    members.iter_mut().find_map(|member| match member {
+        ObjectValue::KeyVal(k, v) if *k == key => Some(&mut v.token),
+        _ => None,
+    })
+}
+
+/// Sorts the keys of every object in the value alphabetically.
+pub(super) fn sort_all(value: &mut Value) {
+    match &mut value.token {
+        ValueToken::Object(members) => {
+            sort_members(members, |key| key);
+            for member in members.iter_mut() {
+                if let ObjectValue::KeyVal(_, v) = member {
+                    sort_all(v);
+                }
+            }
+        }
+        ValueToken::Array(values) => {
+            for v in values.iter_mut() {
+                if let ArrayValue::ArrayVal(v) = v {
+                    sort_all(v);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Stably sorts the members of an object by the provided key. Comments above
+/// a member move along with it, while blank lines stay in place.
+fn sort_members<'a, K: Ord>(
+    members: &mut Vec<ObjectValue<'a>>,
+    mut sort_key: impl FnMut(&'a str) -> K,
+) {
+    let mut entries = Vec::new();
+    let mut blanks = Vec::new();
+    let mut comments = Vec::new();
+    let mut blank = false;
+    for member in members.drain(..) {
+        match member {
+            ObjectValue::Metadata(Metadata::Newline) => blank = true,
+            ObjectValue::Metadata(meta) => comments.push(meta),
+            ObjectValue::KeyVal(key, value) => {
+                entries.push((std::mem::take(&mut comments), key, value));
+                blanks.push(std::mem::take(&mut blank));
+            }
+        }
+    }
+
+    entries.sort_by_cached_key(|(_, key, _)| sort_key(key));
+
+    for ((comments, key, value), blank) in entries.into_iter().zip(blanks) {
+        if blank {
+            members.push(ObjectValue::Metadata(Metadata::Newline));
+        }
+        members.extend(comments.into_iter().map(ObjectValue::Metadata));
+        members.push(ObjectValue::KeyVal(key, value));
+    }
+    // Comments after the last member remain at the end of the object.
+    if blank {
+        members.push(ObjectValue::Metadata(Metadata::Newline));
+    }
+    members.extend(comments.into_iter().map(ObjectValue::Metadata));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_keeps_order() {
+        let input = "{\"b\": 1, \"a\": 2}";
+        assert_eq!(
+            Json::default().format(input).unwrap(),
+            "{\n  \"b\": 1,\n  \"a\": 2\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_layout() {
+        let json = Json {
+            indent: Indent::Tab,
+            line_width: 40,
+            array_values_per_line: 100,
+            object_pairs_per_line: 2,
+            ..Json::default()
+        };
+        let input =
+            r#"{"matrix": [[1, 2, 3, 4, 5, 6], [7, 8, 9, 10, 11, 12]], "point": {"x": 1, "y": 2}}"#;
+        let want = "{\n\t\"matrix\": [\n\t\t[1, 2, 3, 4, 5, 6],\n\t\t[7, 8, 9, 10, 11, 12]\n\t],\n\t\"point\": { \"x\": 1, \"y\": 2 }\n}\n";
+        assert_eq!(json.format(input).unwrap(), want);
+    }
+
+    #[test]
+    fn test_indent_config() {
+        let parse = |input: &str| toml_edit::de::from_str::<Json>(input).map(|json| json.indent);
+        assert_eq!(parse("indent = 4").unwrap(), Indent::Spaces(4));
+        assert_eq!(parse("indent = \"tab\"").unwrap(), Indent::Tab);
+        assert!(parse("indent = \"tabs\"").is_err());
+    }
+
+    #[test]
+    fn test_trailing_commas() {
+        let json = Json {
+            trailing_commas: true,
+            ..Json::default()
+        };
+        let input =
+            "{\"a\": [1, 2], \"b\": {\"c\": [1, 2, 3, 4, 5]}, // comment\n\"d\": \"x\" // last\n}";
+        let want = "{\n  \"a\": [1, 2],\n  \"b\": {\n    \"c\": [\n      1,\n      2,\n      3,\n      4,\n      5,\n    ],\n  }, // comment\n  \"d\": \"x\", // last\n}\n";
+        assert_eq!(json.format_file(Path::new("a.jsonc"), input).unwrap(), want);
+        let want = json.format_file(Path::new("a.jsonc"), input).unwrap();
+        assert_eq!(json.format_file(Path::new("a.jsonc"), &want).unwrap(), want);
+        assert!(!json.format(input).unwrap().contains(",\n}"));
+    }
+
+    #[test]
+    fn test_strict_error() {
+        let input = "{\n  \"a\": [1, 2,], // comment\n}\n";
+        let err = Json::default()
+            .format_file(Path::new("a.json"), input)
+            .unwrap_err();
+        assert_eq!(
+            err,
+            "trailing comma is not allowed in JSON at line 2 column 13\n\
+             trailing comma is not allowed in JSON at line 2 column 15\n\
+             comment is not allowed in JSON at line 2 column 17"
+        );
+        assert!(Json::default()
+            .format_file(Path::new("a.jsonc"), input)
+            .is_ok());
+        assert!(Json::default().format(input).is_ok());
+    }
+
+    #[test]
+    fn test_strict_json_with_comments() {
+        let input =
+            "{\n  // Emit ES modules.\n  \"compilerOptions\": {\"module\": \"esnext\"},\n}\n";
+        let want =
+            "{\n  // Emit ES modules.\n  \"compilerOptions\": { \"module\": \"esnext\" }\n}\n";
+        let json = Json::default();
+        for path in [
+            "tsconfig.json",
+            "packages/app/tsconfig.build.json",
+            "jsconfig.json",
+            ".vscode/settings.json",
+        ] {
+            assert_eq!(json.format_file(Path::new(path), input).unwrap(), want);
+        }
+        assert!(json.format_file(Path::new("settings.json"), input).is_err());
+    }
+
+    #[test]
+    fn test_strict_fix() {
+        let json = Json {
+            strict: Strict::Fix,
+            ..Json::default()
+        };
+        let input = "// header\n{\n  \"a\": 1, // a\n\n  // b\n\n  \"b\": [1, /* c */ 2,],\n}\n";
+        let want = "{\n  \"a\": 1,\n\n  \"b\": [1, 2]\n}\n";
+        assert_eq!(json.format_file(Path::new("a.json"), input).unwrap(), want);
+    }
+
+    #[test]
+    fn test_strict_off() {
+        let json = Json {
+            strict: Strict::Off,
+            ..Json::default()
+        };
+        let input = "{\n  \"a\": 1 // a\n}\n";
+        assert_eq!(json.format_file(Path::new("a.json"), input).unwrap(), input);
+    }
+
+    #[test]
+    fn test_sort_keys() {
+        let json = Json {
+            sort_keys: true,
+            ..Json::default()
+        };
+        let input = r#"{
+  // About c.
+  "c": {"z": 1, "y": [{"b": 1, "a": 2}]}, // trailing
+  "a": 1,
+
+  /* About b. */
+  "b": 2
+  // end
+}"#;
+        let want = r#"{
+  "a": 1,
+  /* About b. */
+  "b": 2,
+
+  // About c.
+  "c": {
+    "y": [
+      {
+        "a": 2,
+        "b": 1
+      }
+    ],
+    "z": 1
+  }, // trailing
+  // end
+}
+"#;
+        assert_eq!(json.format(input).unwrap(), want);
+    }
+
+    #[test]
+    fn test_key_order() {
+        let json = Json {
+            key_order: HashMap::from([(
+                "data.json".to_string(),
+                vec!["id".to_string(), "name".to_string()],
+            )]),
+            ..Json::default()
+        };
+        let input = "{\"extra\": 1, \"name\": \"x\", \"id\": 2}";
+        let want = "{\n  \"id\": 2,\n  \"name\": \"x\",\n  \"extra\": 1\n}\n";
+        assert_eq!(
+            json.format_file(Path::new("a/data.json"), input).unwrap(),
+            want
+        );
+        assert_eq!(
+            json.format(input).unwrap(),
+            json.format_file(Path::new("b.json"), input).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_package_json_preset() {
+        let json = Json {
+            presets: true,
+            ..Json::default()
+        };
+        let input = r#"{
+  "scripts": {"test": "jest", "build": "tsc"},
+  "dependencies": {"react": "^18", "lodash": "^4"},
+  "custom": true,
+  "version": "1.0.0",
+  "name": "x"
+}"#;
+        let want = r#"{
+  "name": "x",
+  "version": "1.0.0",
+  "scripts": {
+    "test": "jest",
+    "build": "tsc"
+  },
+  "dependencies": {
+    "lodash": "^4",
+    "react": "^18"
+  },
+  "custom": true
+}
+"#;
+        let path = Path::new("package.json");
+        assert_eq!(json.format_file(path, input).unwrap(), want);
+        assert_ne!(Json::default().format_file(path, input).unwrap(), want);
+    }
+
+    #[test]
+    fn test_duplicate_keys() {
+        let input = "{\n  \"a\": 1,\n  \"b\": { \"a\": 2 },\n  // \"a\": 2,\n  \"a\": 3\n}\n";
+        assert_eq!(
+            Json::default().format(input).unwrap_err(),
+            "duplicate key \"a\" at line 5 column 3, first defined at line 2 column 3"
+        );
+
+        let json = Json {
+            duplicate_keys: DuplicateKeys::Warn,
+            ..Json::default()
+        };
+        assert_eq!(json.format(input).unwrap(), input);
+        assert_eq!(json.warnings(None, input).len(), 1);
     }
 }
@@ -0,0 +1,764 @@
+use std::{iter::Peekable, path::Path, str::CharIndices};
+
+use serde::Deserialize;
+
+use super::{
+    duplicates::{Checker, DuplicateKeys},
+    Format, Indent, Locator,
+};
+
+/// Json5 formats JSON5 documents. JSON5-only syntax, such as unquoted keys,
+/// single-quoted strings and hexadecimal numbers, is kept as written.
+#[derive(Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Json5 {
+    /// Reports keys that appear more than once in the same object.
+    pub duplicate_keys: DuplicateKeys,
+
+    /// The indentation and line width, which are configured by the `json`
+    /// options. See `Formatters::link`.
+    #[serde(skip)]
+    indent: Indent,
+    #[serde(skip)]
+    line_width: usize,
+}
+
+impl Default for Json5 {
+    fn default() -> Self {
+        Json5 {
+            duplicate_keys: DuplicateKeys::default(),
+            indent: Indent::default(),
+            line_width: 80,
+        }
+    }
+}
+
+impl Format for Json5 {
+    fn format(&self, input: &str) -> Result<String, String> {
+        let parser = Parser::new(input)?;
+        let duplicates = match self.duplicate_keys {
+            DuplicateKeys::Error => duplicate_keys(input, &parser.tokens),
+            DuplicateKeys::Warn => Vec::new(),
+        };
+        let doc = parser.parse_document()?;
+        if !duplicates.is_empty() {
+            return Err(duplicates.join("\n"));
+        }
+        let mut writer = Writer {
+            indent: self.indent.to_str(),
+            line_width: self.line_width,
+            out: String::with_capacity(input.len() + 128),
+        };
+        writer.write_document(&doc);
+        Ok(writer.out)
+    }
+
+    fn warnings(&self, _path: Option<&Path>, input: &str) -> Vec<String> {
+        match (self.duplicate_keys, scan(input)) {
+            (DuplicateKeys::Warn, Ok(tokens)) => duplicate_keys(input, &tokens),
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl Json5 {
+    /// Sets the indentation and line width of the output.
+    #[cfg(feature = "json")]
+    pub(super) fn set_layout(&mut self, indent: Indent, line_width: usize) {
+        self.indent = indent;
+        self.line_width = line_width;
+    }
+}
+
+// --- Scanner ---
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum TokenKind {
+    Punct(char),
+    Scalar,
+    Identifier,
+    Comment,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Token<'a> {
+    kind: TokenKind,
+    text: &'a str,
+    offset: usize,
+    /// The number of line breaks between the previous token and this one.
+    newlines: usize,
+}
+
+fn scan(input: &str) -> Result<Vec<Token<'_>>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+    let mut newlines = 0;
+    while let Some((start, c)) = chars.next() {
+        let kind = match c {
+            '\n' => {
+                newlines += 1;
+                continue;
+            }
+            c if c.is_whitespace() || c == '\u{FEFF}' => continue,
+            '{' | '}' | '[' | ']' | ',' | ':' => TokenKind::Punct(c),
+            '/' => {
+                scan_comment(input, start, &mut chars)?;
+                TokenKind::Comment
+            }
+            '"' | '\'' => {
+                scan_string(input, start, c, &mut chars)?;
+                TokenKind::Scalar
+            }
+            '+' | '-' | '.' | '0'..='9' => {
+                scan_number(input, start, c, &mut chars)?;
+                TokenKind::Scalar
+            }
+            c if is_identifier_start(c) => {
+                scan_identifier(input, start, &mut chars)?;
+                TokenKind::Identifier
+            }
+            c => return Err(unexpected(input, start, c)),
+        };
+        let end = chars.peek().map_or(input.len(), |(i, _)| *i);
+        tokens.push(Token {
+            kind,
+            text: &input[start..end],
+            offset: start,
+            newlines,
+        });
+        newlines = 0;
+    }
+    Ok(tokens)
+}
+
+type Chars<'a> = Peekable<CharIndices<'a>>;
+
+/// Returns a message for every key that appears more than once in the same
+/// object. Quoted and unquoted keys with the same name are the same key.
+fn duplicate_keys(input: &str, tokens: &[Token]) -> Vec<String> {
+    let mut checker = Checker::default();
+    let mut locator = Locator::new(input, 1);
+    let mut objects = Vec::new();
+    let mut expect_key = false;
+    for token in tokens {
+        match token.kind {
+            TokenKind::Punct(open @ ('{' | '[')) => {
+                checker.open(open == '{');
+                objects.push(open == '{');
+                expect_key = open == '{';
+            }
+            TokenKind::Punct('}' | ']') => {
+                checker.close();
+                objects.pop();
+                expect_key = false;
+            }
+            TokenKind::Punct(',') => expect_key = objects.last() == Some(&true),
+            TokenKind::Comment => {}
+            TokenKind::Scalar | TokenKind::Identifier if expect_key => {
+                let key = match token.text.starts_with(['"', '\'']) {
+                    true => &token.text[1..token.text.len() - 1],
+                    false => token.text,
+                };
+                let (line, column) = locator.locate(token.offset);
+                checker.key(key, line, column);
+                expect_key = false;
+            }
+            _ => expect_key = false,
+        }
+    }
+    checker.finish()
+}
+
+fn scan_comment(input: &str, start: usize, chars: &mut Chars) -> Result<(), String> {
+    match chars.next() {
+        Some((_, '/')) => {
+            // The comment ends before the line break, including the '\r' of
+            // CRLF line endings.
+            while chars.next_if(|(_, c)| !matches!(c, '\n' | '\r')).is_some() {}
+            Ok(())
+        }
+        Some((_, '*')) => {
+            let mut prev = ' ';
+            for (_, c) in chars.by_ref() {
+                if prev == '*' && c == '/' {
+                    return Ok(());
+                }
+                prev = c;
+            }
+            Err(format!(
+                "unterminated comment at {}",
+                position(input, start)
+            ))
+        }
+        _ => Err(unexpected(input, start, '/')),
+    }
+}
+
+fn scan_string(input: &str, start: usize, quote: char, chars: &mut Chars) -> Result<(), String> {
+    while let Some((i, c)) = chars.next() {
+        match c {
+            // Any character can be escaped, including line breaks.
+            '\\' => {
+                if let Some((_, '\r')) = chars.next() {
+                    chars.next_if(|(_, c)| *c == '\n');
+                }
+            }
+            '\n' | '\r' => return Err(unexpected(input, i, c)),
+            c if c == quote => return Ok(()),
+            _ => {}
+        }
+    }
+    Err(format!("unterminated string at {}", position(input, start)))
+}
+
+fn scan_number(input: &str, start: usize, first: char, chars: &mut Chars) -> Result<(), String> {
+    let mut first = first;
+    if first == '+' || first == '-' {
+        match chars.next() {
+            Some((_, c)) if c.is_ascii_digit() || c == '.' => first = c,
+            Some((i, c)) if is_identifier_start(c) => {
+                scan_identifier(input, i, chars)?;
+                let end = chars.peek().map_or(input.len(), |(i, _)| *i);
+                return match &input[i..end] {
+                    "Infinity" | "NaN" => Ok(()),
+                    _ => Err(unexpected(input, i, c)),
+                };
+            }
+            Some((i, c)) => return Err(unexpected(input, i, c)),
+            None => return Err("unexpected end of file".to_string()),
+        }
+    }
+
+    let invalid = || format!("invalid number at {}", position(input, start));
+    if first == '0' && chars.next_if(|(_, c)| matches!(c, 'x' | 'X')).is_some() {
+        if skip_digits(chars, 16) == 0 {
+            return Err(invalid());
+        }
+    } else {
+        // Either the integer or the fraction must have a digit, e.g. "1." or
+        // ".5".
+        let mut digits = usize::from(first != '.') + skip_digits(chars, 10);
+        if first != '.' && chars.next_if(|(_, c)| *c == '.').is_some() {
+            digits += skip_digits(chars, 10);
+        }
+        if digits == 0 {
+            return Err(invalid());
+        }
+        if chars.next_if(|(_, c)| matches!(c, 'e' | 'E')).is_some() {
+            chars.next_if(|(_, c)| matches!(c, '+' | '-'));
+            if skip_digits(chars, 10) == 0 {
+                return Err(invalid());
+            }
+        }
+    }
+
+    // A number can't run into another number or an identifier, as in "1-2"
+    // or "1.2.3".
+    match chars.peek() {
+        Some(&(_, c)) if is_identifier_part(c) || matches!(c, '.' | '+' | '-') => Err(invalid()),
+        _ => Ok(()),
+    }
+}
+
+/// Consumes the digits of the provided radix, returning how many there were.
+fn skip_digits(chars: &mut Chars, radix: u32) -> usize {
+    let mut n = 0;
+    while chars.next_if(|(_, c)| c.is_digit(radix)).is_some() {
+        n += 1;
+    }
+    n
+}
+
+fn scan_identifier(input: &str, start: usize, chars: &mut Chars) -> Result<(), String> {
+    if input[start..].starts_with('\\') {
+        scan_unicode_escape(input, start, chars)?;
+    }
+    while let Some((i, c)) = chars.next_if(|(_, c)| is_identifier_part(*c)) {
+        if c == '\\' {
+            scan_unicode_escape(input, i, chars)?;
+        }
+    }
+    Ok(())
+}
+
+fn scan_unicode_escape(input: &str, start: usize, chars: &mut Chars) -> Result<(), String> {
+    if chars.next_if(|(_, c)| *c == 'u').is_none() {
+        return Err(format!("invalid escape at {}", position(input, start)));
+    }
+    for _ in 0..4 {
+        if chars.next_if(|(_, c)| c.is_ascii_hexdigit()).is_none() {
+            return Err(format!("invalid escape at {}", position(input, start)));
+        }
+    }
+    Ok(())
+}
+
+fn is_identifier_start(c: char) -> bool {
+    c.is_alphabetic() || matches!(c, '$' | '_' | '\\')
+}
+
+fn is_identifier_part(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '$' | '_' | '\\' | '\u{200C}' | '\u{200D}')
+}
+
+fn position(input: &str, offset: usize) -> String {
+    let (line, column) = Locator::new(input, 1).locate(offset);
+    format!("line {line} column {column}")
+}
+
+fn unexpected(input: &str, offset: usize, c: char) -> String {
+    format!("unexpected character '{c}' at {}", position(input, offset))
+}
+
+// --- Parser ---
+
+/// Comment is a comment on its own line.
+struct Comment<'a> {
+    text: &'a str,
+    blank_before: bool,
+}
+
+/// Entry is a member of an object or an element of an array.
+struct Entry<'a> {
+    comments: Vec<Comment<'a>>,
+    /// Whether a blank line separates the entry from the preceding comment
+    /// or entry.
+    blank_before: bool,
+    key: Option<&'a str>,
+    value: Node<'a>,
+    /// Comments on the same line as the entry.
+    trailing: Vec<&'a str>,
+}
+
+enum Node<'a> {
+    Scalar(&'a str),
+    Container {
+        open: char,
+        entries: Vec<Entry<'a>>,
+        /// Comments before the closing bracket.
+        comments: Vec<Comment<'a>>,
+    },
+}
+
+struct Document<'a> {
+    comments: Vec<Comment<'a>>,
+    blank_before: bool,
+    value: Node<'a>,
+    trailing: Vec<&'a str>,
+    end_comments: Vec<Comment<'a>>,
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    tokens: Vec<Token<'a>>,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a str) -> Result<Self, String> {
+        Ok(Parser {
+            input,
+            tokens: scan(input)?,
+            pos: 0,
+        })
+    }
+
+    fn peek(&self) -> Option<Token<'a>> {
+        self.tokens.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Result<Token<'a>, String> {
+        let token = self.peek().ok_or("unexpected end of file")?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn error(&self, token: Token) -> String {
+        let (line, column) = Locator::new(self.input, 1).locate(token.offset);
+        format!(
+            "unexpected token '{}' at line {line} column {column}",
+            token.text
+        )
+    }
+
+    fn parse_document(mut self) -> Result<Document<'a>, String> {
+        let comments = self.own_line_comments(true);
+        let blank_before = self.peek().is_some_and(|t| t.newlines > 1) && !comments.is_empty();
+        let value = self.parse_value()?;
+        let trailing = self.same_line_comments();
+        let end_comments = self.own_line_comments(false);
+        if let Some(token) = self.peek() {
+            return Err(self.error(token));
+        }
+        Ok(Document {
+            comments,
+            blank_before,
+            value,
+            trailing,
+            end_comments,
+        })
+    }
+
+    /// Collects the comments on the same line as the previous token.
+    fn same_line_comments(&mut self) -> Vec<&'a str> {
+        let mut comments = Vec::new();
+        while let Some(token) = self.peek() {
+            if token.kind != TokenKind::Comment || token.newlines > 0 {
+                break;
+            }
+            comments.push(token.text);
+            self.pos += 1;
+        }
+        comments
+    }
+
+    /// Collects the comments that start on their own line.
+    fn own_line_comments(&mut self, first: bool) -> Vec<Comment<'a>> {
+        let mut comments = Vec::new();
+        while let Some(token) = self.peek() {
+            if token.kind != TokenKind::Comment {
+                break;
+            }
+            comments.push(Comment {
+                text: token.text,
+                blank_before: token.newlines > 1 && !(first && comments.is_empty()),
+            });
+            self.pos += 1;
+        }
+        comments
+    }
+
+    fn parse_value(&mut self) -> Result<Node<'a>, String> {
+        let token = self.next()?;
+        match token.kind {
+            TokenKind::Punct(open @ ('{' | '[')) => self.parse_container(open),
+            TokenKind::Scalar => Ok(Node::Scalar(token.text)),
+            TokenKind::Identifier => match token.text {
+                "true" | "false" | "null" | "Infinity" | "NaN" => Ok(Node::Scalar(token.text)),
+                _ => Err(self.error(token)),
+            },
+            _ => Err(self.error(token)),
+        }
+    }
+
+    fn parse_container(&mut self, open: char) -> Result<Node<'a>, String> {
+        let close = if open == '{' { '}' } else { ']' };
+        let mut entries: Vec<Entry> = Vec::new();
+        loop {
+            // Comments directly after a comma belong to the preceding entry,
+            // while those after the opening bracket are moved to the next line.
+            if let Some(entry) = entries.last_mut() {
+                entry.trailing.extend(self.same_line_comments());
+            }
+            let comments = self.own_line_comments(entries.is_empty());
+
+            let token = self.next()?;
+            if token.kind == TokenKind::Punct(close) {
+                return Ok(Node::Container {
+                    open,
+                    entries,
+                    comments,
+                });
+            }
+            let blank_before = token.newlines > 1 && !(entries.is_empty() && comments.is_empty());
+
+            let mut trailing = Vec::new();
+            let key = match open {
+                '{' => {
+                    let is_key =
+                        token.kind == TokenKind::Identifier || token.text.starts_with(['"', '\'']);
+                    if !is_key {
+                        return Err(self.error(token));
+                    }
+                    trailing.extend(self.comments());
+                    let colon = self.next()?;
+                    if colon.kind != TokenKind::Punct(':') {
+                        return Err(self.error(colon));
+                    }
+                    trailing.extend(self.comments());
+                    Some(token.text)
+                }
+                _ => {
+                    self.pos -= 1;
+                    None
+                }
+            };
+            let value = self.parse_value()?;
+            trailing.extend(self.same_line_comments());
+
+            // Comments on their own line are left for the next entry or the
+            // closing bracket, unless they come before this entry's comma.
+            let rest = &self.tokens[self.pos..];
+            match rest.iter().find(|t| t.kind != TokenKind::Comment) {
+                Some(t) if t.kind == TokenKind::Punct(',') => {
+                    trailing.extend(self.comments());
+                    self.pos += 1;
+                }
+                Some(t) if t.kind == TokenKind::Punct(close) => {}
+                Some(t) => return Err(self.error(*t)),
+                None => return Err("unexpected end of file".to_string()),
+            }
+            entries.push(Entry {
+                comments,
+                blank_before,
+                key,
+                value,
+                trailing,
+            });
+        }
+    }
+
+    /// Collects any comments at the current position.
+    fn comments(&mut self) -> Vec<&'a str> {
+        let mut comments = Vec::new();
+        while let Some(token) = self.peek().filter(|t| t.kind == TokenKind::Comment) {
+            comments.push(token.text);
+            self.pos += 1;
+        }
+        comments
+    }
+}
+
+// --- Writer ---
+
+/// Writer writes a document with the indentation and line width of the
+/// `json` options.
+struct Writer {
+    indent: String,
+    line_width: usize,
+    out: String,
+}
+
+impl Writer {
+    fn write_document(&mut self, doc: &Document) {
+        write_comments(&mut self.out, &doc.comments, "", true);
+        if doc.blank_before {
+            self.out.push('\n');
+        }
+        self.write_node(&doc.value, "");
+        write_trailing(&mut self.out, &doc.trailing);
+        self.out.push('\n');
+        write_comments(&mut self.out, &doc.end_comments, "", false);
+    }
+
+    fn write_node(&mut self, node: &Node, indent: &str) {
+        let (open, entries, comments) = match node {
+            Node::Scalar(text) => return self.out.push_str(text),
+            Node::Container {
+                open,
+                entries,
+                comments,
+            } => (*open, entries, comments),
+        };
+        let close = if open == '{' { '}' } else { ']' };
+
+        if entries.is_empty() && comments.is_empty() {
+            self.out.push(open);
+            self.out.push(close);
+            return;
+        }
+        if let Some(line) = inline_array(node) {
+            let column = self.out[self.out.rfind('\n').map_or(0, |i| i + 1)..]
+                .chars()
+                .count();
+            if column + line.chars().count() <= self.line_width {
+                self.out.push_str(&line);
+                return;
+            }
+        }
+
+        let inner = format!("{indent}{}", self.indent);
+        self.out.push(open);
+        self.out.push('\n');
+        for (i, entry) in entries.iter().enumerate() {
+            write_comments(&mut self.out, &entry.comments, &inner, i == 0);
+            if entry.blank_before {
+                self.out.push('\n');
+            }
+            self.out.push_str(&inner);
+            if let Some(key) = entry.key {
+                self.out.push_str(key);
+                self.out.push_str(": ");
+            }
+            self.write_node(&entry.value, &inner);
+            // Entries on their own line always end with a comma, so that adding
+            // another entry only changes a single line.
+            self.out.push(',');
+            write_trailing(&mut self.out, &entry.trailing);
+            self.out.push('\n');
+        }
+        write_comments(&mut self.out, comments, &inner, entries.is_empty());
+        self.out.push_str(indent);
+        self.out.push(close);
+    }
+}
+
+fn write_comments(out: &mut String, comments: &[Comment], indent: &str, first: bool) {
+    for (i, comment) in comments.iter().enumerate() {
+        if comment.blank_before && !(first && i == 0) {
+            out.push('\n');
+        }
+        out.push_str(indent);
+        out.push_str(comment.text);
+        out.push('\n');
+    }
+}
+
+fn write_trailing(out: &mut String, comments: &[&str]) {
+    for comment in comments {
+        out.push(' ');
+        out.push_str(comment);
+    }
+}
+
+/// Returns the single-line form of an array of scalars without comments.
+fn inline_array(node: &Node) -> Option<String> {
+    let Node::Container {
+        open: '[',
+        entries,
+        comments,
+    } = node
+    else {
+        return None;
+    };
+    if !comments.is_empty() {
+        return None;
+    }
+    let mut values = Vec::with_capacity(entries.len());
+    for entry in entries {
+        match &entry.value {
+            Node::Scalar(text) if entry.comments.is_empty() && entry.trailing.is_empty() => {
+                values.push(*text);
+            }
+            _ => return None,
+        }
+    }
+    Some(format!("[{}]", values.join(", ")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format_json5(input: &str) -> Result<String, String> {
+        Json5::default().format(input)
+    }
+
+    #[test]
+    fn test_json5_syntax() {
+        let input = "{unquoted:'single',hex:0xFF,pos:+Infinity,neg:-.5e3,nan:NaN,\"q\":\"x\",}";
+        let want = "{\n  unquoted: 'single',\n  hex: 0xFF,\n  pos: +Infinity,\n  neg: -.5e3,\n  nan: NaN,\n  \"q\": \"x\",\n}\n";
+        assert_eq!(format_json5(input).unwrap(), want);
+    }
+
+    #[test]
+    fn test_comments() {
+        let input = r#"// Header.
+
+{ // Opening.
+    // About a.
+    a: [1, 2, 3,], // Trailing.
+
+
+    /* About b. */
+    b: { c: true, /* inline */ },
+    // Before close.
+}
+// Footer.
+"#;
+        let want = r#"// Header.
+
+{
+  // Opening.
+  // About a.
+  a: [1, 2, 3], // Trailing.
+
+  /* About b. */
+  b: {
+    c: true, /* inline */
+  },
+  // Before close.
+}
+// Footer.
+"#;
+        assert_eq!(format_json5(input).unwrap(), want);
+        assert_eq!(format_json5(want).unwrap(), want);
+        assert_eq!(format_json5(&input.replace('\n', "\r\n")).unwrap(), want);
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_layout() {
+        let formatters =
+            crate::config::parse("[json]\nindent = \"tab\"\nline-width = 12\n").unwrap();
+        assert_eq!(
+            formatters
+                .json5
+                .format("{a: [1, 2], b: [1, 2, 3, 4]}")
+                .unwrap(),
+            "{\n\ta: [1, 2],\n\tb: [\n\t\t1,\n\t\t2,\n\t\t3,\n\t\t4,\n\t],\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_long_array() {
+        let input = format!("[{}]", ["1234567890"; 10].join(","));
+        let out = format_json5(&input).unwrap();
+        assert!(
+            out.starts_with("[\n  1234567890,\n  1234567890,\n"),
+            "{out}"
+        );
+        assert!(out.ends_with("  1234567890,\n]\n"), "{out}");
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            format_json5("{a: 1,\n b 2}").unwrap_err(),
+            "unexpected token '2' at line 2 column 4"
+        );
+        assert!(format_json5("{a: 'x\n'}").is_err());
+        assert!(format_json5("[1 2]").is_err());
+        assert!(format_json5("[foo]").is_err());
+        assert!(format_json5("{1: 2}").is_err());
+        assert!(format_json5("").is_err());
+        assert!(format_json5("/* x").is_err());
+
+        for (input, column) in [
+            ("[1-2]", 2),
+            ("[1.2.3]", 2),
+            ("[0x]", 2),
+            ("[1e]", 2),
+            ("[1e+]", 2),
+            ("[.]", 2),
+            ("[1, -.e1]", 5),
+            ("[12a]", 2),
+        ] {
+            assert_eq!(
+                format_json5(input).unwrap_err(),
+                format!("invalid number at line 1 column {column}"),
+                "{input}"
+            );
+        }
+        assert_eq!(
+            format_json5("[1, 2.,\n .5e-3, +0x1F, 1E+2]").unwrap(),
+            "[1, 2., .5e-3, +0x1F, 1E+2]\n"
+        );
+    }
+
+    #[test]
+    fn test_duplicate_keys() {
+        let input = "{\n  a: 1,\n  'b': {\n    a: 2,\n  },\n  \"a\": 3,\n}\n";
+        assert_eq!(
+            format_json5(input).unwrap_err(),
+            "duplicate key \"a\" at line 6 column 3, first defined at line 2 column 3"
+        );
+
+        let json5 = Json5 {
+            duplicate_keys: DuplicateKeys::Warn,
+            ..Json5::default()
+        };
+        assert_eq!(json5.format(input).unwrap(), input);
+        assert_eq!(json5.warnings(None, input).len(), 1);
+    }
+}
@@ -0,0 +1,150 @@
+use std::io::{BufRead, Write};
+
+use serde::Deserialize;
+
+use super::{
+    json::{duplicate_keys, sort_all, strict_errors},
+    Format, Locator,
+};
+
+/// The maximum number of line errors reported for a single document.
+const MAX_ERRORS: usize = 20;
+
+/// Jsonl formats JSON Lines (NDJSON) documents, writing each value as compact
+/// JSON on its own line.
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Jsonl {
+    /// Sorts the keys of every object alphabetically.
+    pub sort_keys: bool,
+}
+
+impl Format for Jsonl {
+    fn format(&self, input: &str) -> Result<String, String> {
+        let mut out = Vec::with_capacity(input.len());
+        self.format_stream(&mut input.as_bytes(), &mut out)?;
+        String::from_utf8(out).map_err(|err| err.to_string())
+    }
+
+    fn supports_streaming(&self) -> bool {
+        true
+    }
+
+    fn format_stream(&self, r: &mut dyn BufRead, w: &mut dyn Write) -> Result<(), String> {
+        let mut errors = Vec::new();
+        let mut num_errors = 0;
+        let mut line = String::new();
+        let mut out = String::new();
+        for n in 1.. {
+            line.clear();
+            match r.read_line(&mut line) {
+                Ok(0) => break,
+                Ok(_) => {}
+                Err(err) => return Err(format!("line {n}: {err}")),
+            }
+            // Blank lines aren't allowed between values, so they're removed.
+            let value = line.trim();
+            if value.is_empty() {
+                continue;
+            }
+
+            out.clear();
+            if let Err(errs) = self.format_line(n, value, &mut out) {
+                num_errors += errs.len();
+                errors.extend(errs.into_iter().take(MAX_ERRORS - errors.len()));
+                continue;
+            }
+            if errors.is_empty() {
+                out.push('\n');
+                w.write_all(out.as_bytes()).map_err(|err| err.to_string())?;
+            }
+        }
+
+        if num_errors > errors.len() {
+            errors.push(format!("and {} more errors", num_errors - errors.len()));
+        }
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(errors.join("\n")),
+        }
+    }
+}
+
+impl Jsonl {
+    fn format_line(&self, n: usize, value: &str, out: &mut String) -> Result<(), Vec<String>> {
+        let mut root = fjson::ast::parse(value).map_err(|err| vec![format!("line {n}: {err}")])?;
+
+        let errors = strict_errors(value);
+        if !errors.is_empty() {
+            let mut locator = Locator::new(value, n);
+            return Err(errors
+                .into_iter()
+                .map(|(offset, what)| {
+                    let (line, column) = locator.locate(offset);
+                    format!("{what} is not allowed in JSON at line {line} column {column}")
+                })
+                .collect());
+        }
+        let duplicates = duplicate_keys(value, n);
+        if !duplicates.is_empty() {
+            return Err(duplicates);
+        }
+
+        if self.sort_keys {
+            sort_all(&mut root.value);
+        }
+        fjson::format::write_json_compact(out, &root)
+            .map_err(|err| vec![format!("line {n}: {err}")])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format() {
+        let input = "{ \"b\" : 1,\n\"a\": [1, 2]}\n";
+        assert!(Jsonl::default().format(input).is_err());
+
+        let input = "{ \"b\" : 1, \"a\": [ 1, 2 ] }\n\n  [true,  null]\r\n\"x\"";
+        let want = "{\"b\":1,\"a\":[1,2]}\n[true,null]\n\"x\"\n";
+        assert_eq!(Jsonl::default().format(input).unwrap(), want);
+    }
+
+    #[test]
+    fn test_sort_keys() {
+        let jsonl = Jsonl { sort_keys: true };
+        let input = "{\"b\": 1, \"a\": {\"d\": 2, \"c\": 3}}\n";
+        let want = "{\"a\":{\"c\":3,\"d\":2},\"b\":1}\n";
+        assert_eq!(jsonl.format(input).unwrap(), want);
+    }
+
+    #[test]
+    fn test_line_errors() {
+        let input = "{\"a\": 1}\n{\"a\": }\n[1, 2,]\n{\"a\": 1}\n";
+        let err = Jsonl::default().format(input).unwrap_err();
+        assert_eq!(
+            err,
+            "line 2: unexpected token at index range 6 -> 7: '}'\n\
+             trailing comma is not allowed in JSON at line 3 column 6"
+        );
+    }
+
+    #[test]
+    fn test_error_limit() {
+        let input = "x\n".repeat(MAX_ERRORS + 5);
+        let err = Jsonl::default().format(&input).unwrap_err();
+        assert_eq!(err.lines().count(), MAX_ERRORS + 1);
+        assert!(err.ends_with("and 5 more errors"), "{err}");
+    }
+
+    #[test]
+    fn test_duplicate_keys() {
+        let input = "{\"a\": 1}\n{\"a\": 1, \"b\": {\"a\": 2}, \"a\": 3}\n";
+        assert_eq!(
+            Jsonl::default().format(input).unwrap_err(),
+            "duplicate key \"a\" at line 2 column 25, first defined at line 2 column 2"
+        );
+    }
+}
@@ -1,30 +1,1439 @@
-use super::{json::Json, sql::Sql, toml::Toml, yaml::Yaml, Format};
+use std::{ops::Range, path::Path, str::FromStr};
 
-use cmarkfmt::Formatter;
+#[cfg(feature = "markdown-fences")]
+use std::sync::Arc;
 
-#[derive(Copy, Clone, Default)]
-pub(crate) struct Markdown {
-    json: Json,
-    sql: Sql,
-    toml: Toml,
-    yaml: Yaml,
+use super::{shift_lines, Format, Locator};
+#[cfg(feature = "markdown-fences")]
+use super::{with_linked, Formatters, Kind};
+
+use pulldown_cmark::{
+    Alignment, CodeBlockKind, Event, HeadingLevel, LinkType, Options, Parser, Tag,
+};
+use serde::Deserialize;
+use unicode_width::UnicodeWidthStr;
+
+/// Markdown formats CommonMark and GitHub Flavored Markdown documents, along
+/// with any code blocks written in a language that metafmt can format.
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Markdown {
+    /// The syntax that documents are parsed with.
+    flavor: Flavor,
+    /// Whether code blocks that fail to format are errors, rather than
+    /// warnings.
+    strict: bool,
+    /// How the lines of paragraphs are broken.
+    prose_wrap: ProseWrap,
+    /// The width that paragraphs are wrapped at with `ProseWrap::Always`.
+    line_width: usize,
+
+    /// The formatters used for front matter and code blocks, which are
+    /// configured along with this one. See `Formatters::link`.
+    #[cfg(feature = "markdown-fences")]
+    #[serde(skip)]
+    formatters: Option<Arc<Formatters>>,
+}
+
+impl Default for Markdown {
+    fn default() -> Self {
+        Markdown {
+            flavor: Flavor::default(),
+            strict: false,
+            prose_wrap: ProseWrap::default(),
+            line_width: 80,
+            #[cfg(feature = "markdown-fences")]
+            formatters: None,
+        }
+    }
+}
+
+/// ProseWrap controls how the lines of paragraphs are broken. Headings,
+/// tables, link reference definitions and code blocks are never wrapped.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProseWrap {
+    /// Paragraphs are wrapped to fit within the line width.
+    Always,
+    /// Each paragraph is written on a single line.
+    Never,
+    /// Paragraphs are broken where they are in the input.
+    #[default]
+    Preserve,
+}
+
+impl FromStr for ProseWrap {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "always" => Ok(ProseWrap::Always),
+            "never" => Ok(ProseWrap::Never),
+            "preserve" => Ok(ProseWrap::Preserve),
+            _ => Err(format!(
+                "invalid prose wrap '{s}', expected always, never or preserve"
+            )),
+        }
+    }
+}
+
+/// Flavor selects the Markdown syntax that documents are parsed with.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Flavor {
+    /// CommonMark, without any extensions.
+    Commonmark,
+    /// GitHub Flavored Markdown, which adds tables, task lists, strikethrough
+    /// and footnotes to CommonMark. Heading attributes, e.g. `{ #id }`, are
+    /// recognized as well.
+    #[default]
+    Gfm,
+}
+
+impl Flavor {
+    /// Returns the options that documents of the flavor are parsed with.
+    fn options(self) -> Options {
+        match self {
+            Flavor::Commonmark => Options::empty(),
+            Flavor::Gfm => {
+                Options::ENABLE_TABLES
+                    | Options::ENABLE_FOOTNOTES
+                    | Options::ENABLE_STRIKETHROUGH
+                    | Options::ENABLE_TASKLISTS
+                    | Options::ENABLE_HEADING_ATTRIBUTES
+            }
+        }
+    }
 }
 
 impl Format for Markdown {
     fn format(&self, input: &str) -> Result<String, String> {
-        Ok(Formatter::default()
-            .with_code_formatter(Some(&|lang, code| {
-                match lang {
-                    "json" | "jsonc" | "hjson" | "jwcc" => self.json.format(code),
-                    "md" => self.format(code),
-                    "sql" => self.sql.format(code),
-                    "toml" => self.toml.format(code),
-                    "yml" | "yaml" => self.yaml.format(code),
-                    _ => return None,
-                }
-                .map(Some)
-                .unwrap_or(None)
-            }))
-            .format_cmark(input))
+        self.format_with_warnings(None, input).map(|(out, _)| out)
+    }
+
+    fn warnings(&self, path: Option<&Path>, input: &str) -> Vec<String> {
+        self.format_with_warnings(path, input)
+            .map(|(_, warnings)| warnings)
+            .unwrap_or_default()
+    }
+
+    fn format_with_warnings(
+        &self,
+        _path: Option<&Path>,
+        input: &str,
+    ) -> Result<(String, Vec<String>), String> {
+        let (out, problems) = self.format_document(input, 1)?;
+        if self.strict && !problems.is_empty() {
+            return Err(problems.join("\n"));
+        }
+        Ok((out, problems))
+    }
+}
+
+/// FrontMatter is the metadata at the start of a document, between lines of
+/// "---" for YAML or "+++" for TOML.
+struct FrontMatter<'a> {
+    #[cfg_attr(not(feature = "markdown-fences"), allow(dead_code))]
+    lang: &'static str,
+    open: &'a str,
+    content: &'a str,
+    close: &'a str,
+    body: &'a str,
+}
+
+impl<'a> FrontMatter<'a> {
+    /// Returns the front matter at the start of the input, if any.
+    fn parse(input: &'a str) -> Option<Self> {
+        let (open, rest) = input.split_once('\n')?;
+        let open = open.trim_end();
+        // YAML documents may also end with "...".
+        let (lang, closes): (_, &[&str]) = match open {
+            "---" => ("yaml", &["---", "..."]),
+            "+++" => ("toml", &["+++"]),
+            _ => return None,
+        };
+        let mut pos = 0;
+        for line in rest.split_inclusive('\n') {
+            let close = line.trim_end();
+            if closes.contains(&close) {
+                return Some(FrontMatter {
+                    lang,
+                    open,
+                    content: &rest[..pos],
+                    close,
+                    body: &rest[pos + line.len()..],
+                });
+            }
+            pos += line.len();
+        }
+        None
+    }
+}
+
+impl Markdown {
+    /// Formats a document whose first line is numbered `first_line`,
+    /// returning it along with the problems found in its code blocks.
+    fn format_document(
+        &self,
+        input: &str,
+        first_line: usize,
+    ) -> Result<(String, Vec<String>), String> {
+        let Some(front) = FrontMatter::parse(input) else {
+            return Ok(self.format_body(input, first_line));
+        };
+        let content = self.format_front_matter(&front).map_err(|err| {
+            let err = file_positions(&err, front.content, first_line, 0);
+            format!("front matter: {err}")
+        })?;
+
+        let mut out = format!("{}\n{content}", front.open);
+        if !content.is_empty() && !content.ends_with('\n') {
+            out.push('\n');
+        }
+        out.push_str(front.close);
+        out.push('\n');
+        let skipped = input[..input.len() - front.body.len()]
+            .matches('\n')
+            .count();
+        let (body, problems) = self.format_body(front.body, first_line + skipped);
+        if !body.is_empty() {
+            out.push('\n');
+            out.push_str(&body);
+        }
+        Ok((out, problems))
+    }
+
+    /// Formats the front matter's content with the formatter for its
+    /// language, or returns it as written if that formatter isn't compiled in.
+    fn format_front_matter(&self, front: &FrontMatter) -> Result<String, String> {
+        if front.content.trim().is_empty() {
+            return Ok(String::new());
+        }
+        #[cfg(feature = "markdown-fences")]
+        if let Some(kind) = Kind::from_name(front.lang).filter(Kind::is_available) {
+            return with_linked(self.formatters.as_deref(), |formatters| {
+                formatters.get(kind).format(front.content)
+            });
+        }
+        Ok(front.content.to_string())
+    }
+
+    /// Formats a document without front matter, returning it along with the
+    /// problems found in its code blocks.
+    fn format_body(&self, input: &str, first_line: usize) -> (String, Vec<String>) {
+        let parser = Parser::new_ext(input, self.flavor.options());
+        let (out, problems) = Renderer::new(self, input, first_line).render(parser);
+        (self.wrap_prose(out), problems)
+    }
+
+    /// Sets how paragraphs are wrapped, keeping the configured value of each
+    /// option that is None.
+    pub fn set_prose_wrap(&mut self, prose_wrap: Option<ProseWrap>, line_width: Option<usize>) {
+        self.prose_wrap = prose_wrap.unwrap_or(self.prose_wrap);
+        self.line_width = line_width.unwrap_or(self.line_width);
+    }
+
+    /// Sets the formatters used for front matter and code blocks.
+    #[cfg(feature = "markdown-fences")]
+    pub(super) fn set_formatters(&mut self, formatters: Arc<Formatters>) {
+        self.formatters = Some(formatters);
+    }
+
+    /// Formats the content of a fenced code block whose first line is
+    /// numbered `first_line`, returning None if metafmt has no formatter for
+    /// its language. Problems in the code blocks of embedded Markdown are
+    /// added to `problems`.
+    #[cfg(feature = "markdown-fences")]
+    fn format_code(
+        &self,
+        lang: &str,
+        code: &str,
+        first_line: usize,
+        problems: &mut Vec<String>,
+    ) -> Option<Result<String, String>> {
+        with_linked(self.formatters.as_deref(), |formatters| {
+            let formatter = formatters.for_embedded(lang)?;
+            if !formatters.external.iter().any(|v| v.handles(lang))
+                && Kind::from_name(lang) == Some(Kind::Markdown)
+            {
+                // Embedded Markdown is formatted with this formatter, so that
+                // the problems in its own code blocks are reported.
+                let result = self.format_document(code, first_line);
+                return Some(result.map(|(out, nested)| {
+                    problems.extend(nested);
+                    out
+                }));
+            }
+            Some(formatter.format(code))
+        })
+    }
+}
+
+/// Converts the positions in an error from the formatter of embedded content,
+/// which are relative to that content, into positions in the file. The
+/// content starts on the line after `line`, and is indented by `indent`
+/// columns.
+fn file_positions(err: &str, content: &str, line: usize, indent: usize) -> String {
+    // Source snippets, such as TOML's "2 | b =", quote the content with its
+    // own line numbers, and are dropped.
+    let is_snippet = |text: &str| {
+        let rest = text.trim_start_matches(|c: char| c.is_ascii_digit() || c == ' ');
+        rest.starts_with('|') && rest.len() < text.len()
+    };
+    let message = err
+        .lines()
+        .filter(|text| !is_snippet(text))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    // Byte offsets that come with a line and column, such as YAML's "byte 7
+    // line 2 column 3", are dropped.
+    let mut out = String::with_capacity(message.len());
+    let mut rest = message.as_str();
+    while let Some(i) = rest.find("byte ") {
+        out.push_str(&rest[..i]);
+        let after = &rest[i + "byte ".len()..];
+        match split_number(after) {
+            Some((_, tail)) if tail.starts_with(" line ") => rest = &tail[1..],
+            _ => {
+                out.push_str("byte ");
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+
+    // Other byte offsets, such as JSON's "index range 6 -> 7", are written
+    // as a line and column.
+    let message = out;
+    let mut out = String::with_capacity(message.len());
+    let mut rest = message.as_str();
+    while let Some(i) = rest.find("index ") {
+        out.push_str(&rest[..i]);
+        let after = &rest[i + "index ".len()..];
+        let range = after.strip_prefix("range ");
+        match split_number(range.unwrap_or(after)) {
+            Some((offset, tail)) if content.is_char_boundary(offset) => {
+                let (line, column) = Locator::new(content, 1).locate(offset);
+                out.push_str(&format!("line {line} column {column}"));
+                // The end of a range is dropped along with its start.
+                let end = range.and(tail.strip_prefix(" -> ")).and_then(split_number);
+                rest = end.map_or(tail, |(_, tail)| tail);
+            }
+            _ => {
+                out.push_str("index ");
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+
+    shift_columns(&shift_lines(&out, line), indent)
+}
+
+/// Splits the number at the start of the text from the rest of it.
+fn split_number(text: &str) -> Option<(usize, &str)> {
+    let digits = text.len() - text.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let number = text[..digits].parse().ok()?;
+    Some((number, &text[digits..]))
+}
+
+/// Adds the offset to every column number in the message, e.g. "column 3",
+/// for content that is indented in the file.
+fn shift_columns(message: &str, offset: usize) -> String {
+    if offset == 0 {
+        return message.to_string();
+    }
+    let mut out = String::with_capacity(message.len());
+    let mut rest = message;
+    while let Some(i) = rest.find("column ") {
+        let (before, after) = rest.split_at(i + "column ".len());
+        out.push_str(before);
+        rest = match split_number(after) {
+            Some((column, tail)) => {
+                out.push_str(&(column + offset).to_string());
+                tail
+            }
+            None => after,
+        };
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Returns the language of a code block from its info string, e.g. "yaml" for
+/// `yaml title="config.yml"` or "json" for the attributes `{.json}`.
+#[cfg(feature = "markdown-fences")]
+fn language(info: &str) -> Option<String> {
+    let info = info.trim();
+    let lang = match info.strip_prefix('{') {
+        Some(attrs) => attrs
+            .trim_end_matches('}')
+            .split_whitespace()
+            .find_map(|attr| attr.strip_prefix('.'))?,
+        None => info
+            .split(|c: char| c.is_whitespace() || matches!(c, '{' | ',' | ':'))
+            .next()?,
+    };
+    match lang.is_empty() {
+        true => None,
+        false => Some(lang.to_lowercase()),
+    }
+}
+
+/// Run is the inline content of a paragraph, or of an item in a tight list,
+/// between any hard line breaks and nested blocks.
+struct Run {
+    range: Range<usize>,
+    /// The prefix of each line after the first, e.g. "> " in a blockquote.
+    prefix: String,
+    /// The ranges of code spans and inline HTML, which are never broken.
+    atoms: Vec<Range<usize>>,
+    /// The offsets of hard line breaks.
+    breaks: Vec<usize>,
+}
+
+impl Markdown {
+    /// Rewraps the paragraphs of a rendered document, according to the
+    /// configured ProseWrap.
+    fn wrap_prose(&self, doc: String) -> String {
+        if self.prose_wrap == ProseWrap::Preserve {
+            return doc;
+        }
+
+        let mut runs = Vec::new();
+        let mut run: Option<Run> = None;
+        // The open blocks, along with the prefix of their continuation lines.
+        let mut blocks: Vec<(Tag, String)> = Vec::new();
+        for (event, range) in Parser::new_ext(&doc, self.flavor.options()).into_offset_iter() {
+            let inline = match &event {
+                Event::Start(tag) | Event::End(tag) => is_inline(tag),
+                // Blocks of HTML are reported a line at a time.
+                Event::Html(html) => !html.ends_with('\n'),
+                Event::Text(_)
+                | Event::Code(_)
+                | Event::SoftBreak
+                | Event::HardBreak
+                | Event::FootnoteReference(_) => true,
+                Event::Rule | Event::TaskListMarker(_) => false,
+            };
+            if inline && matches!(blocks.last(), Some((Tag::Paragraph | Tag::Item, _))) {
+                let run = run.get_or_insert_with(|| Run {
+                    range: range.clone(),
+                    prefix: blocks.iter().map(|(_, prefix)| prefix.as_str()).collect(),
+                    atoms: Vec::new(),
+                    breaks: Vec::new(),
+                });
+                run.range.end = run.range.end.max(range.end);
+                match event {
+                    Event::Code(_) | Event::Html(_) => run.atoms.push(range),
+                    Event::HardBreak => run.breaks.push(range.start),
+                    _ => {}
+                }
+                continue;
+            }
+
+            runs.extend(run.take());
+            match event {
+                Event::Start(tag) if !inline => {
+                    let prefix = match tag {
+                        Tag::BlockQuote => "> ".to_string(),
+                        // Lines within an item are indented by the width of
+                        // its marker, e.g. "- " or "1. ".
+                        Tag::Item => {
+                            let line = doc[range.start..].lines().next().unwrap_or_default();
+                            " ".repeat(line.find(' ').map_or(line.len(), |i| i + 1))
+                        }
+                        _ => String::new(),
+                    };
+                    blocks.push((tag, prefix));
+                }
+                Event::End(_) if !inline => {
+                    blocks.pop();
+                }
+                _ => {}
+            }
+        }
+
+        let mut out = doc.clone();
+        for run in runs.iter_mut().rev() {
+            // Whitespace before a nested block separates it from the run.
+            run.range.end = run.range.start + doc[run.range.clone()].trim_end().len();
+            out.replace_range(run.range.clone(), &self.wrap_run(&doc, run));
+        }
+        out
+    }
+
+    /// Returns the run's content with its lines broken according to the
+    /// configured ProseWrap.
+    fn wrap_run(&self, doc: &str, run: &Run) -> String {
+        let line_start = doc[..run.range.start].rfind('\n').map_or(0, |i| i + 1);
+        let mut width = doc[line_start..run.range.start].width();
+        let mut out = String::with_capacity(run.range.len());
+        for (n, segment) in segments(doc, run).iter().enumerate() {
+            if n > 0 {
+                out.push('\n');
+                out.push_str(&run.prefix);
+                width = run.prefix.width();
+            }
+            for (i, word) in segment.iter().enumerate() {
+                if i == 0 {
+                    out.push_str(word);
+                    width += word.width();
+                    continue;
+                }
+                // "#", "-" and "+" are only escaped at the start of a line, so
+                // the escape follows the word when it moves.
+                let bare = word
+                    .strip_prefix('\\')
+                    .filter(|word| word.starts_with(['#', '-', '+']))
+                    .unwrap_or(word);
+                // Words that would start a block, and lines that would end
+                // with a backslash (a hard line break), are not broken.
+                let wrap = self.prose_wrap == ProseWrap::Always
+                    && width + 1 + bare.width() > self.line_width
+                    && can_start_line(bare)
+                    && !out.ends_with('\\');
+                if wrap {
+                    out.push('\n');
+                    out.push_str(&run.prefix);
+                    width = run.prefix.width();
+                    if bare.starts_with(['#', '-', '+']) {
+                        out.push('\\');
+                        width += 1;
+                    }
+                } else {
+                    out.push(' ');
+                    width += 1;
+                }
+                out.push_str(bare);
+                width += bare.width();
+            }
+        }
+        out
+    }
+}
+
+/// Returns true for tags that occur within a block, such as emphasis.
+fn is_inline(tag: &Tag) -> bool {
+    matches!(
+        tag,
+        Tag::Emphasis | Tag::Strong | Tag::Strikethrough | Tag::Link(..) | Tag::Image(..)
+    )
+}
+
+/// Splits the content of a run into the words of each line that ends with a
+/// hard line break. Code spans and inline HTML are kept as single words.
+fn segments(doc: &str, run: &Run) -> Vec<Vec<String>> {
+    let mut segments: Vec<Vec<String>> = vec![Vec::new()];
+    let mut word = String::new();
+    let mut pos = run.range.start;
+    // Skips the prefix of the line starting at the offset.
+    let skip_prefix = |pos: usize| match doc[pos..].starts_with(&run.prefix) {
+        true => pos + run.prefix.len(),
+        false => doc.len() - doc[pos..].trim_start_matches(' ').len(),
+    };
+    while pos < run.range.end {
+        if let Some(atom) = run.atoms.iter().find(|atom| atom.start == pos) {
+            word.push_str(&doc[atom.clone()]);
+            pos = atom.end;
+            continue;
+        }
+        let segment = segments.last_mut().unwrap();
+        if run.breaks.contains(&pos) {
+            match segment.last_mut() {
+                Some(last) if word.is_empty() => last.push('\\'),
+                _ => segment.push(std::mem::take(&mut word) + "\\"),
+            }
+            segments.push(Vec::new());
+            pos = match doc[pos..].find('\n') {
+                Some(i) => skip_prefix(pos + i + 1),
+                None => run.range.end,
+            };
+            continue;
+        }
+        let c = doc[pos..].chars().next().unwrap_or(' ');
+        pos += c.len_utf8();
+        match c {
+            ' ' | '\n' => {
+                if !word.is_empty() {
+                    segment.push(std::mem::take(&mut word));
+                }
+                if c == '\n' {
+                    pos = skip_prefix(pos);
+                }
+            }
+            c => word.push(c),
+        }
+    }
+    if !word.is_empty() {
+        segments.last_mut().unwrap().push(word);
+    }
+    segments
+}
+
+/// Returns false for words that would start a block if they were at the start
+/// of a line, such as "#" (a heading) or "1." (a list item).
+fn can_start_line(word: &str) -> bool {
+    // Markers of headings, list items, thematic breaks and setext underlines.
+    let marker = word
+        .chars()
+        .next()
+        .is_some_and(|c| "#*+-=_".contains(c) && word.chars().all(|v| v == c));
+    let digits = word.trim_start_matches(|c: char| c.is_ascii_digit());
+    let list_item = digits.len() < word.len() && (digits == "." || digits == ")");
+    let footnote = word.starts_with("[^") && word.contains("]:");
+    let fence = word.starts_with("```") || word.starts_with("~~~");
+    !(marker || list_item || footnote || fence || word.starts_with(['>', '<', '|']))
+}
+
+// --- Rendering ---
+
+const STRONG: &str = "**";
+const EMPHASIS: &str = "_";
+const STRIKETHROUGH: &str = "~~";
+
+/// Container is a block whose lines are prefixed, e.g. with "> ".
+enum Container {
+    BlockQuote,
+    IndentedCode,
+    List {
+        /// The number of the first item of an ordered list.
+        start: Option<String>,
+        /// Whether the marker of the current item has been written.
+        marker_written: bool,
+        /// Whether the current item continues after a line break.
+        newline: bool,
+    },
+}
+
+/// Reference is a link reference definition, written at the end of the
+/// document.
+struct Reference {
+    label: String,
+    dest: String,
+    title: Option<String>,
+}
+
+/// Table holds the cells of a table until all of them are known, so that its
+/// columns can be aligned.
+struct Table {
+    alignments: Vec<Alignment>,
+    head: Vec<String>,
+    body: Vec<Vec<String>>,
+}
+
+impl Table {
+    /// Returns the display width of each column, which is at least 3.
+    fn widths(&self) -> Vec<usize> {
+        (0..self.head.len())
+            .map(|i| {
+                self.body
+                    .iter()
+                    .chain([&self.head])
+                    .filter_map(|row| row.get(i))
+                    .map(|cell| cell.width())
+                    .fold(3, usize::max)
+            })
+            .collect()
+    }
+}
+
+/// Renderer writes the events of a parsed document back out as Markdown.
+struct Renderer<'a> {
+    #[cfg_attr(not(feature = "markdown-fences"), allow(dead_code))]
+    md: &'a Markdown,
+    out: String,
+    /// The current line, without the prefixes of its containers.
+    line: String,
+    containers: Vec<Container>,
+    references: Vec<Reference>,
+    table: Option<Table>,
+    code: Option<CodeBlock>,
+    newline_required: bool,
+    last_line_blank: bool,
+    /// Finds the lines of code blocks in the input.
+    locator: Locator<'a>,
+    /// The code blocks that failed to format.
+    problems: Vec<String>,
+}
+
+/// CodeBlock holds the content of a code block until its end, so that it can
+/// be formatted as a whole.
+struct CodeBlock {
+    /// The info string of a fenced code block.
+    info: Option<String>,
+    content: String,
+    /// The line that the code block starts on.
+    #[cfg_attr(not(feature = "markdown-fences"), allow(dead_code))]
+    line: usize,
+    /// The number of columns that the code block is indented by, e.g. in a
+    /// list item.
+    #[cfg_attr(not(feature = "markdown-fences"), allow(dead_code))]
+    indent: usize,
+}
+
+impl<'a> Renderer<'a> {
+    fn new(md: &'a Markdown, input: &'a str, first_line: usize) -> Self {
+        Renderer {
+            md,
+            out: String::new(),
+            line: String::new(),
+            containers: Vec::new(),
+            references: Vec::new(),
+            table: None,
+            code: None,
+            newline_required: false,
+            last_line_blank: true,
+            locator: Locator::new(input, first_line),
+            problems: Vec::new(),
+        }
+    }
+
+    /// Renders the document, returning it along with the problems found in
+    /// its code blocks.
+    fn render(mut self, parser: Parser) -> (String, Vec<String>) {
+        self.references = parser
+            .reference_definitions()
+            .iter()
+            .map(|(label, def)| Reference {
+                label: label.to_string(),
+                dest: def.dest.to_string(),
+                title: def.title.as_ref().map(|title| title.to_string()),
+            })
+            .collect();
+        self.references.sort_by(|a, b| a.label.cmp(&b.label));
+
+        let mut after_html = false;
+        for (event, range) in parser.into_offset_iter() {
+            // Blocks of HTML end at a blank line.
+            if after_html
+                && !matches!(
+                    event,
+                    Event::Html(_) | Event::Text(_) | Event::SoftBreak | Event::End(_)
+                )
+            {
+                self.newline();
+            }
+            after_html = matches!(event, Event::Html(_));
+
+            match event {
+                Event::Start(tag) => self.start(tag, range.start),
+                Event::End(tag) => self.end(tag),
+                Event::Text(text) => match &mut self.code {
+                    Some(code) => code.content.push_str(&text),
+                    None => {
+                        self.escape(&text);
+                        self.line.push_str(&text);
+                    }
+                },
+                Event::Code(code) => {
+                    self.line.push('`');
+                    if code.starts_with('`') {
+                        self.line.push('\\');
+                    }
+                    self.line.push_str(&code);
+                    self.line.push('`');
+                }
+                Event::Html(html) => {
+                    if self.line.is_empty() {
+                        self.newline_if_required();
+                    }
+                    self.line.push_str(&html);
+                    if html.ends_with('\n') {
+                        self.newline();
+                    }
+                }
+                Event::SoftBreak => self.newline(),
+                Event::HardBreak => {
+                    self.line.push('\\');
+                    self.write_lines(false);
+                }
+                Event::Rule => {
+                    if self.newline_required {
+                        self.newline();
+                    }
+                    self.line.push_str("---");
+                    self.newline();
+                    self.newline_required = true;
+                }
+                Event::TaskListMarker(checked) => {
+                    self.line.push_str(if checked { "[x] " } else { "[ ] " });
+                }
+                Event::FootnoteReference(label) => {
+                    self.line.push_str("[^");
+                    self.line.push_str(&label);
+                    self.line.push(']');
+                }
+            }
+        }
+
+        let references = std::mem::take(&mut self.references);
+        if !references.is_empty() {
+            self.newline();
+            for reference in references {
+                self.line.push('[');
+                self.line.push_str(&reference.label);
+                self.line.push_str("]: ");
+                self.line.push_str(&reference.dest);
+                if let Some(title) = reference.title {
+                    self.line.push_str(" \"");
+                    self.line.push_str(&title);
+                    self.line.push('"');
+                }
+                self.newline();
+            }
+        }
+        (self.out, self.problems)
+    }
+
+    fn start(&mut self, tag: Tag, offset: usize) {
+        self.newline_if_required();
+        match tag {
+            Tag::Heading(level, _, _) => {
+                let level = match level {
+                    HeadingLevel::H1 => 1,
+                    HeadingLevel::H2 => 2,
+                    HeadingLevel::H3 => 3,
+                    HeadingLevel::H4 => 4,
+                    HeadingLevel::H5 => 5,
+                    HeadingLevel::H6 => 6,
+                };
+                self.line.push_str(&"#".repeat(level));
+                self.line.push(' ');
+            }
+            Tag::BlockQuote => self.containers.push(Container::BlockQuote),
+            Tag::CodeBlock(kind) => {
+                if !self.line.is_empty() {
+                    self.newline();
+                }
+                let info = match kind {
+                    CodeBlockKind::Indented => {
+                        self.containers.push(Container::IndentedCode);
+                        None
+                    }
+                    CodeBlockKind::Fenced(info) => Some(info.to_string()),
+                };
+                let (line, column) = self.locator.locate(offset);
+                self.code = Some(CodeBlock {
+                    info,
+                    content: String::new(),
+                    line,
+                    indent: column - 1,
+                });
+            }
+            Tag::List(start) => {
+                if let Some(Container::List { newline, .. }) = self.containers.last_mut() {
+                    *newline = true;
+                    self.newline();
+                }
+                self.containers.push(Container::List {
+                    start: start.map(|n| n.to_string()),
+                    marker_written: false,
+                    newline: false,
+                });
+            }
+            Tag::Item => {
+                if let Some(Container::List {
+                    marker_written,
+                    newline,
+                    ..
+                }) = self.containers.last_mut()
+                {
+                    *marker_written = false;
+                    *newline = false;
+                }
+            }
+            Tag::FootnoteDefinition(label) => {
+                self.line.push_str("[^");
+                self.line.push_str(&label);
+                self.line.push_str("]: ");
+            }
+            Tag::Table(alignments) => {
+                self.table = Some(Table {
+                    alignments,
+                    head: Vec::new(),
+                    body: Vec::new(),
+                })
+            }
+            Tag::TableRow => {
+                if let Some(table) = &mut self.table {
+                    table.body.push(Vec::with_capacity(table.head.len()));
+                }
+            }
+            Tag::Emphasis => self.line.push_str(EMPHASIS),
+            Tag::Strong => self.line.push_str(STRONG),
+            Tag::Strikethrough => self.line.push_str(STRIKETHROUGH),
+            Tag::Link(LinkType::Autolink | LinkType::Email, _, _) => self.line.push('<'),
+            Tag::Link(..) => self.line.push('['),
+            Tag::Image(..) => self.line.push_str("!["),
+            Tag::Paragraph | Tag::TableHead | Tag::TableCell => {}
+        }
+    }
+
+    fn end(&mut self, tag: Tag) {
+        match tag {
+            Tag::Paragraph => {
+                match self.containers.last_mut() {
+                    Some(Container::List { newline, .. }) => *newline = true,
+                    _ => self.newline_required = true,
+                }
+                self.newline_if_content();
+            }
+            Tag::Heading(_, id, classes) => {
+                if id.is_some() || !classes.is_empty() {
+                    self.line.push('{');
+                    if let Some(id) = id {
+                        self.line.push_str(" #");
+                        self.line.push_str(id);
+                    }
+                    for class in classes {
+                        self.line.push_str(" .");
+                        self.line.push_str(class);
+                    }
+                    self.line.push_str(" }");
+                }
+                self.newline_required = true;
+                self.newline();
+            }
+            Tag::BlockQuote => {
+                self.containers.pop();
+                if !matches!(self.containers.last(), Some(Container::List { .. })) {
+                    self.newline_required = true;
+                }
+            }
+            Tag::CodeBlock(kind) => {
+                let Some(CodeBlock {
+                    info,
+                    content: mut code,
+                    line,
+                    indent,
+                }) = self.code.take()
+                else {
+                    return;
+                };
+                #[cfg(feature = "markdown-fences")]
+                if let Some(lang) = info.as_deref().and_then(language) {
+                    match self
+                        .md
+                        .format_code(&lang, &code, line + 1, &mut self.problems)
+                    {
+                        Some(Ok(out)) => code = out,
+                        Some(Err(err)) => self.problems.push(format!(
+                            "{lang} code block at line {line}: {}",
+                            file_positions(&err, &code, line, indent)
+                        )),
+                        None => {}
+                    }
+                }
+                #[cfg(not(feature = "markdown-fences"))]
+                let _ = (line, indent);
+                if !code.is_empty() && !code.ends_with('\n') {
+                    code.push('\n');
+                }
+                match kind {
+                    CodeBlockKind::Fenced(_) => {
+                        // The fence must be longer than any in the code.
+                        let fence = code
+                            .lines()
+                            .map(|line| {
+                                let line = line.trim_start();
+                                line.len() - line.trim_start_matches('`').len() + 1
+                            })
+                            .fold(3, usize::max);
+                        let fence = "`".repeat(fence);
+                        self.line.push_str(&fence);
+                        self.line.push_str(info.as_deref().unwrap_or_default());
+                        self.newline();
+                        self.line.push_str(&code);
+                        self.line.push_str(&fence);
+                        self.newline();
+                    }
+                    CodeBlockKind::Indented => {
+                        self.line.push_str(&code);
+                        self.newline();
+                        self.containers.pop();
+                    }
+                }
+                self.newline_required = true;
+            }
+            Tag::List(_) => {
+                self.containers.pop();
+                if !self
+                    .containers
+                    .iter()
+                    .any(|container| matches!(container, Container::List { .. }))
+                {
+                    self.newline_required = true;
+                }
+            }
+            Tag::Item => {
+                if let Some(Container::List { newline: false, .. }) = self.containers.last() {
+                    self.newline_if_content();
+                }
+            }
+            Tag::Table(_) => {
+                if let Some(table) = self.table.take() {
+                    self.write_table(table);
+                }
+                self.newline_required = true;
+                if let Some(Container::List { newline, .. }) = self.containers.last_mut() {
+                    *newline = true;
+                }
+            }
+            Tag::TableCell => {
+                if let Some(table) = &mut self.table {
+                    let cell = std::mem::take(&mut self.line);
+                    match table.body.last_mut() {
+                        Some(row) => row.push(cell),
+                        None => table.head.push(cell),
+                    }
+                }
+            }
+            Tag::Emphasis => self.line.push_str(EMPHASIS),
+            Tag::Strong => self.line.push_str(STRONG),
+            Tag::Strikethrough => self.line.push_str(STRIKETHROUGH),
+            Tag::Link(LinkType::Reference | LinkType::ReferenceUnknown, dest, title) => {
+                match self
+                    .references
+                    .iter()
+                    .find(|reference| dest.eq_ignore_ascii_case(&reference.dest))
+                {
+                    Some(reference) => {
+                        self.line.push_str("][");
+                        self.line.push_str(&reference.label);
+                        self.line.push(']');
+                    }
+                    None => self.write_destination(&dest, &title),
+                }
+            }
+            Tag::Link(LinkType::Shortcut | LinkType::ShortcutUnknown, ..) => self.line.push(']'),
+            Tag::Link(LinkType::Collapsed | LinkType::CollapsedUnknown, ..) => {
+                self.line.push_str("][]")
+            }
+            Tag::Link(LinkType::Autolink | LinkType::Email, ..) => self.line.push('>'),
+            Tag::Link(_, dest, title) | Tag::Image(_, dest, title) => {
+                self.write_destination(&dest, &title)
+            }
+            Tag::FootnoteDefinition(_) | Tag::TableHead | Tag::TableRow => {}
+        }
+    }
+
+    /// Writes the end of an inline link or image, e.g. `](url "title")`.
+    fn write_destination(&mut self, dest: &str, title: &str) {
+        self.line.push_str("](");
+        self.line.push_str(dest);
+        if !title.is_empty() {
+            self.line.push_str(" \"");
+            self.line.push_str(title);
+            self.line.push('"');
+        }
+        self.line.push(')');
+    }
+
+    /// Writes a backslash if the text would otherwise be read as markup.
+    fn escape(&mut self, text: &str) {
+        let Some(first) = text.chars().next() else {
+            return;
+        };
+        let escape = match first {
+            '|' => self.table.is_some(),
+            '\\' | '<' | '>' | '*' | '_' | '`' | '[' | ']' | '~' => true,
+            '#' | '-' | '+' => self.line.is_empty(),
+            _ => false,
+        };
+        if escape {
+            self.line.push('\\');
+        }
+    }
+
+    /// Writes a table with its columns padded to the same width, aligning the
+    /// content of each cell according to its column's alignment.
+    fn write_table(&mut self, table: Table) {
+        let widths = table.widths();
+        self.write_row(&table.head, &widths, &table.alignments);
+
+        self.line.push('|');
+        for (width, alignment) in widths.iter().zip(&table.alignments) {
+            let (left, right) = match alignment {
+                Alignment::None => ('-', '-'),
+                Alignment::Left => (':', '-'),
+                Alignment::Center => (':', ':'),
+                Alignment::Right => ('-', ':'),
+            };
+            self.line.push(' ');
+            self.line.push(left);
+            self.line.push_str(&"-".repeat(width - 2));
+            self.line.push(right);
+            self.line.push_str(" |");
+        }
+        self.newline();
+
+        for row in &table.body {
+            self.write_row(row, &widths, &table.alignments);
+        }
+    }
+
+    fn write_row(&mut self, row: &[String], widths: &[usize], alignments: &[Alignment]) {
+        self.line.push('|');
+        for ((cell, width), alignment) in row.iter().zip(widths).zip(alignments) {
+            let padding = width - cell.width();
+            let left = match alignment {
+                Alignment::None | Alignment::Left => 0,
+                Alignment::Center => padding / 2,
+                Alignment::Right => padding,
+            };
+            self.line.push(' ');
+            self.line.push_str(&" ".repeat(left));
+            self.line.push_str(cell);
+            self.line.push_str(&" ".repeat(padding - left));
+            self.line.push_str(" |");
+        }
+        self.newline();
+    }
+
+    fn newline_if_required(&mut self) {
+        if self.newline_required {
+            self.newline();
+            self.newline_required = false;
+        }
+    }
+
+    fn newline_if_content(&mut self) {
+        if !self.line.is_empty() || !self.containers.is_empty() {
+            self.newline();
+        }
+    }
+
+    /// Ends the current line, removing any trailing whitespace.
+    fn newline(&mut self) {
+        self.write_lines(true);
+    }
+
+    /// Writes each line of the current line buffer with the prefixes of its
+    /// containers. Consecutive blank lines are written as one.
+    fn write_lines(&mut self, trim: bool) {
+        let buf = std::mem::take(&mut self.line);
+        if buf.is_empty() {
+            self.write_line("", trim);
+        }
+        for line in buf.lines() {
+            self.write_line(line, trim);
+        }
+    }
+
+    fn write_line(&mut self, line: &str, trim: bool) {
+        let mut full = self.prefix();
+        full.push_str(line);
+        let full = if trim { full.trim_end() } else { &full };
+        if !full.is_empty() || !self.last_line_blank {
+            self.out.push_str(full);
+            self.out.push('\n');
+        }
+        self.last_line_blank = full.is_empty();
+    }
+
+    /// Returns the prefix of the next line, writing the marker of a list item
+    /// on its first line.
+    fn prefix(&mut self) -> String {
+        let mut prefix = String::new();
+        for container in &mut self.containers {
+            match container {
+                Container::BlockQuote => prefix.push_str("> "),
+                Container::IndentedCode => prefix.push_str("    "),
+                Container::List {
+                    start,
+                    marker_written,
+                    ..
+                } => {
+                    let marker = match start {
+                        Some(n) => format!("{n}. "),
+                        None => "- ".to_string(),
+                    };
+                    match marker_written {
+                        true => prefix.push_str(&" ".repeat(marker.len())),
+                        false => prefix.push_str(&marker),
+                    }
+                    *marker_written = true;
+                }
+            }
+        }
+        prefix
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prose_wrap() {
+        let input = "# A heading that is longer than the width of the lines\n\n\
+                     Some text that is long enough to be wrapped, with `a code span` and\n\
+                     a [link](https://example.com \"its title\").\\\n\
+                     After a break, #1 and - stay put.\n\n\
+                     > Quoted text that is long enough to be wrapped as well.\n\n\
+                     - An item in a list that is long enough to be wrapped.\n\
+                     - [ ] A task that is long enough to be wrapped as well.\n\n\
+                     | a table with a long row | b |\n\
+                     | ----------------------- | - |\n";
+
+        let md = Markdown {
+            prose_wrap: ProseWrap::Always,
+            line_width: 30,
+            ..Markdown::default()
+        };
+        let want = "# A heading that is longer than the width of the lines\n\n\
+                    Some text that is long enough\n\
+                    to be wrapped, with\n\
+                    `a code span` and a\n\
+                    [link](https://example.com\n\
+                    \"its title\").\\\n\
+                    After a break, #1 and - stay\n\
+                    put.\n\n\
+                    > Quoted text that is long\n\
+                    > enough to be wrapped as\n\
+                    > well.\n\n\
+                    - An item in a list that is\n  \
+                    long enough to be wrapped.\n\
+                    - [ ] A task that is long\n  \
+                    enough to be wrapped as\n  \
+                    well.\n\n\
+                    | a table with a long row | b   |\n\
+                    | ----------------------- | --- |\n";
+        let out = md.format(input).unwrap();
+        assert_eq!(out, want);
+        assert_eq!(md.format(&out).unwrap(), out);
+
+        let md = Markdown {
+            prose_wrap: ProseWrap::Never,
+            ..Markdown::default()
+        };
+        let want = "# A heading that is longer than the width of the lines\n\n\
+                    Some text that is long enough to be wrapped, with `a code span` and a \
+                    [link](https://example.com \"its title\").\\\n\
+                    After a break, #1 and - stay put.\n\n\
+                    > Quoted text that is long enough to be wrapped as well.\n\n\
+                    - An item in a list that is long enough to be wrapped.\n\
+                    - [ ] A task that is long enough to be wrapped as well.\n\n\
+                    | a table with a long row | b   |\n\
+                    | ----------------------- | --- |\n";
+        assert_eq!(md.format(&out).unwrap(), want);
+    }
+
+    #[test]
+    fn test_prose_wrap_block_start() {
+        let md = Markdown {
+            prose_wrap: ProseWrap::Always,
+            line_width: 10,
+            ..Markdown::default()
+        };
+        assert_eq!(
+            md.format("Issue #123 and 2. or - or\n").unwrap(),
+            "Issue #123\nand 2. or -\nor\n"
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "markdown-fences", feature = "toml", feature = "yaml"))]
+    fn test_front_matter() {
+        let md = Markdown::default();
+        let input = "---\ntitle:   Hello\ntags: [a,b]\n---\n# Heading\nText\n";
+        let want = "---\ntitle: Hello\ntags: [a,b]\n---\n\n# Heading\n\nText\n";
+        assert_eq!(md.format(input).unwrap(), want);
+        assert_eq!(md.format(want).unwrap(), want);
+
+        let input = "+++\ntitle='Hello'\n+++\nText\n";
+        let want = "+++\ntitle = 'Hello'\n+++\n\nText\n";
+        assert_eq!(md.format(input).unwrap(), want);
+
+        assert_eq!(md.format("---\na: 1\n...\n").unwrap(), "---\na: 1\n...\n");
+        assert_eq!(md.format("---\n---\nText").unwrap(), "---\n---\n\nText\n");
+
+        let err = md.format("---\na: 1\nb: [1\n---\nText\n").unwrap_err();
+        assert!(err.starts_with("front matter: "), "{err}");
+        assert!(err.contains("line 4"), "{err}");
+
+        // A thematic break without a closing line is not front matter.
+        assert_eq!(md.format("---\nText\n").unwrap(), "---\n\nText\n");
+    }
+
+    #[test]
+    fn test_tables() {
+        let md = Markdown::default();
+        let input = "| Name | 説明 | Price | Notes |\n\
+                     |:-----|:----:|------:|---|\n\
+                     | 苹果 | フルーツ | 1 | a \\| b |\n\
+                     | pear | x | 100 | |\n";
+        let want = "| Name |   説明   | Price | Notes  |\n\
+                    | :--- | :------: | ----: | ------ |\n\
+                    | 苹果 | フルーツ |     1 | a \\| b |\n\
+                    | pear |    x     |   100 |        |\n";
+        let out = md.format(input).unwrap();
+        assert_eq!(out, want);
+        assert_eq!(md.format(&out).unwrap(), out);
+    }
+
+    #[test]
+    fn test_gfm() {
+        let md = Markdown::default();
+        let input = "- [X] Done\n-   [ ]   To do\n\n\
+                     ~~Struck~~ and ~struck~.\n\n\
+                     A note[^1] and <https://example.com>, or https://example.com/a_b.\n\n\
+                     [^1]: The note.\n";
+        let want = "- [x] Done\n- [ ] To do\n\n\
+                    ~~Struck~~ and ~~struck~~.\n\n\
+                    A note[^1] and <https://example.com>, or https://example.com/a_b.\n\n\
+                    [^1]: The note.\n";
+        assert_eq!(md.format(input).unwrap(), want);
+    }
+
+    #[test]
+    fn test_flavor() {
+        let input = "| a | b |\n| - | - |\n\n- [ ] Task\n";
+        let md = Markdown {
+            flavor: Flavor::Commonmark,
+            ..Markdown::default()
+        };
+        let want = "| a | b |\n| - | - |\n\n- \\[ \\] Task\n";
+        assert_eq!(md.format(input).unwrap(), want);
+        assert_eq!(md.format(want).unwrap(), want);
+
+        let want = "| a   | b   |\n| --- | --- |\n\n- [ ] Task\n";
+        assert_eq!(Markdown::default().format(input).unwrap(), want);
+    }
+
+    #[test]
+    #[cfg(all(feature = "markdown-fences", feature = "json"))]
+    fn test_code_blocks() {
+        let md = Markdown::default();
+        let input = "- Item\n  ```json\n  {\"a\":\n  1}\n  ```\n\n\
+                     ````md\n```\ncode\n```\n````\n";
+        let want = "- Item\n  ```json\n  {\n    \"a\": 1\n  }\n  ```\n\n\
+                    ````md\n```\ncode\n```\n````\n";
+        let out = md.format(input).unwrap();
+        assert_eq!(out, want);
+        assert_eq!(md.format(&out).unwrap(), out);
+    }
+
+    #[test]
+    #[cfg(all(feature = "markdown-fences", feature = "json", feature = "yaml"))]
+    fn test_code_block_problems() {
+        let input = "---\ntitle: x\n---\n# Title\n\n```yaml\na: [1\n```\n\n\
+                     - Item\n\n  ```json\n  {\"a\": }\n  ```\n\n\
+                     ````md\n```json\n[\n```\n````\n";
+        let md = Markdown::default();
+        let want = "---\ntitle: x\n---\n\n# Title\n\n```yaml\na: [1\n```\n\n\
+                    - Item\n  ```json\n  {\"a\": }\n  ```\n\n\
+                    ````md\n```json\n[\n```\n````\n";
+        assert_eq!(md.format(input).unwrap(), want);
+
+        let warnings = md.warnings(None, input);
+        assert_eq!(warnings.len(), 3, "{warnings:?}");
+        assert!(warnings[0].starts_with("yaml code block at line 6: "));
+        assert!(warnings[0].ends_with("line 8 column 1"));
+        assert!(warnings[1].starts_with("json code block at line 12: "));
+        assert!(warnings[2].starts_with("json code block at line 17: "));
+        assert_eq!(
+            md.format_with_warnings(None, input).unwrap(),
+            (want.to_string(), warnings.clone())
+        );
+
+        let md = Markdown {
+            strict: true,
+            ..Markdown::default()
+        };
+        assert_eq!(md.format(input).unwrap_err(), warnings.join("\n"));
+        assert!(md.warnings(None, input).is_empty());
+    }
+
+    #[test]
+    #[cfg(all(
+        feature = "markdown-fences",
+        feature = "json",
+        feature = "toml",
+        feature = "yaml"
+    ))]
+    fn test_code_block_positions() {
+        let input = "# Title\n\n```toml\na = 1\nb =\n```\n\n\
+                     ```yaml\na: 1\nb: [1\nc: 2\n```\n\n\
+                     - Item\n\n  ```json\n  {\"a\": }\n  ```\n";
+        let warnings = Markdown::default().warnings(None, input);
+        assert_eq!(warnings.len(), 3, "{warnings:?}");
+        assert!(warnings[0].starts_with("toml code block at line 3: "));
+        assert!(
+            warnings[0].contains("at line 5, column 4"),
+            "{}",
+            warnings[0]
+        );
+        assert!(!warnings[0].contains('|'), "{}", warnings[0]);
+        assert!(warnings[1].starts_with("yaml code block at line 8: "));
+        assert!(!warnings[1].contains("byte"), "{}", warnings[1]);
+        assert!(
+            warnings[1].contains("at line 11 column 2"),
+            "{}",
+            warnings[1]
+        );
+        assert_eq!(
+            warnings[2],
+            "json code block at line 16: unexpected token at line 17 column 9: '}'"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "markdown-fences")]
+    fn test_language() {
+        assert_eq!(
+            language("yaml title=\"config.yml\"").as_deref(),
+            Some("yaml")
+        );
+        assert_eq!(language("{.json}").as_deref(), Some("json"));
+        assert_eq!(
+            language("{#example .json .numberLines}").as_deref(),
+            Some("json")
+        );
+        assert_eq!(language("js{1,3}").as_deref(), Some("js"));
+        assert_eq!(language("JSON,linenos").as_deref(), Some("json"));
+        assert_eq!(language(" toml:Cargo.toml").as_deref(), Some("toml"));
+        assert_eq!(language("{#example}"), None);
+        assert_eq!(language(" "), None);
+    }
+
+    #[test]
+    #[cfg(all(
+        feature = "markdown-fences",
+        feature = "json",
+        feature = "json5",
+        feature = "jsonl"
+    ))]
+    fn test_code_block_info_strings() {
+        let md = Markdown::default();
+        let input = "```json title=\"a.json\"\n{\"a\":1}\n```\n\n\
+                     ``` {.json5}\n{a:1}\n```\n\n\
+                     ```jsonl\n{\"a\": 1}\n\n{\"b\": 2}\n```\n\n\
+                     ```diff\n-a\n+b\n```\n";
+        let want = "```json title=\"a.json\"\n{\n  \"a\": 1\n}\n```\n\n\
+                    ```{.json5}\n{\n  a: 1,\n}\n```\n\n\
+                    ```jsonl\n{\"a\":1}\n{\"b\":2}\n```\n\n\
+                    ```diff\n-a\n+b\n```\n";
+        assert_eq!(md.format(input).unwrap(), want);
+    }
+
+    #[test]
+    #[cfg(feature = "markdown-fences")]
+    fn test_code_block_external() {
+        let formatters = Formatters {
+            external: vec![super::super::external::External {
+                extensions: vec!["txt".to_string()],
+                command: "tr".to_string(),
+                args: vec!["a-z".to_string(), "A-Z".to_string()],
+                ..Default::default()
+            }],
+            ..Formatters::default()
+        }
+        .link();
+        let input = "```txt\nhello\n```\n\n```text\nhello\n```\n";
+        let want = "```txt\nHELLO\n```\n\n```text\nhello\n```\n";
+        assert_eq!(formatters.markdown.format(input).unwrap(), want);
     }
 }
@@ -1,9 +1,629 @@
+#[cfg(any(feature = "markdown-fences", feature = "yaml"))]
+use std::sync::Arc;
+use std::{
+    io::{BufRead, Write},
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Deserializer};
+
+use self::external::External;
+
+#[cfg(feature = "dotenv")]
+pub mod dotenv;
+#[cfg(any(
+    feature = "dotenv",
+    feature = "json",
+    feature = "json5",
+    feature = "yaml"
+))]
+pub mod duplicates;
+pub mod external;
+#[cfg(feature = "ini")]
+pub mod ini;
+#[cfg(feature = "json")]
 pub mod json;
+#[cfg(feature = "json5")]
+pub mod json5;
+#[cfg(feature = "jsonl")]
+pub mod jsonl;
+#[cfg(feature = "markdown")]
 pub mod markdown;
+#[cfg(feature = "sql")]
 pub mod sql;
+#[cfg(feature = "toml")]
 pub mod toml;
+#[cfg(feature = "xml")]
+pub mod xml;
+#[cfg(feature = "yaml")]
 pub mod yaml;
 
-pub(crate) trait Format {
+// Formatters whose cargo feature is disabled are replaced by IgnoredAny, so
+// that configuration files remain valid across builds.
+#[cfg(feature = "dotenv")]
+use self::dotenv::Dotenv;
+#[cfg(feature = "ini")]
+use self::ini::Ini;
+#[cfg(feature = "json")]
+use self::json::Json;
+#[cfg(feature = "json5")]
+use self::json5::Json5;
+#[cfg(feature = "jsonl")]
+use self::jsonl::Jsonl;
+#[cfg(feature = "markdown")]
+use self::markdown::Markdown;
+#[cfg(feature = "sql")]
+use self::sql::Sql;
+#[cfg(feature = "toml")]
+use self::toml::Toml;
+#[cfg(feature = "xml")]
+use self::xml::Xml;
+#[cfg(feature = "yaml")]
+use self::yaml::Yaml;
+#[cfg(not(feature = "dotenv"))]
+use serde::de::IgnoredAny as Dotenv;
+#[cfg(not(feature = "ini"))]
+use serde::de::IgnoredAny as Ini;
+#[cfg(not(feature = "json"))]
+use serde::de::IgnoredAny as Json;
+#[cfg(not(feature = "json5"))]
+use serde::de::IgnoredAny as Json5;
+#[cfg(not(feature = "jsonl"))]
+use serde::de::IgnoredAny as Jsonl;
+#[cfg(not(feature = "markdown"))]
+use serde::de::IgnoredAny as Markdown;
+#[cfg(not(feature = "sql"))]
+use serde::de::IgnoredAny as Sql;
+#[cfg(not(feature = "toml"))]
+use serde::de::IgnoredAny as Toml;
+#[cfg(not(feature = "xml"))]
+use serde::de::IgnoredAny as Xml;
+#[cfg(not(feature = "yaml"))]
+use serde::de::IgnoredAny as Yaml;
+
+/// Format is implemented by every formatter, turning the contents of a file
+/// into its formatted form or returning a description of why it could not be
+/// parsed.
+pub trait Format {
     fn format(&self, input: &str) -> Result<String, String>;
+
+    /// Formats the contents of the file at the provided path. Formatters that
+    /// treat specific files differently (e.g. Cargo.toml) override this.
+    fn format_file(&self, path: &Path, input: &str) -> Result<String, String> {
+        let _ = path;
+        self.format(input)
+    }
+
+    /// Returns true if a fragment of a document (e.g. a selection in an
+    /// editor) can be formatted independently of its surroundings.
+    fn supports_range(&self) -> bool {
+        false
+    }
+
+    /// Returns true if the formatter processes its input incrementally in
+    /// `format_stream`, rather than holding the whole document in memory.
+    fn supports_streaming(&self) -> bool {
+        false
+    }
+
+    /// Returns problems in the input that don't prevent it from being
+    /// formatted, such as duplicate keys that are configured as warnings. The
+    /// path of the file is provided if it is known.
+    fn warnings(&self, path: Option<&Path>, input: &str) -> Vec<String> {
+        let _ = (path, input);
+        Vec::new()
+    }
+
+    /// Formats the input and returns its warnings together. Formatters that
+    /// find their warnings while formatting override this, so that callers
+    /// needing both only format the input once.
+    fn format_with_warnings(
+        &self,
+        path: Option<&Path>,
+        input: &str,
+    ) -> Result<(String, Vec<String>), String> {
+        let out = match path {
+            Some(path) => self.format_file(path, input)?,
+            None => self.format(input)?,
+        };
+        Ok((out, self.warnings(path, input)))
+    }
+
+    /// Formats the input read from `r`, writing the result to `w`.
+    fn format_stream(&self, r: &mut dyn BufRead, w: &mut dyn Write) -> Result<(), String> {
+        let mut input = String::new();
+        r.read_to_string(&mut input)
+            .map_err(|err| err.to_string())?;
+        let out = self.format(&input)?;
+        w.write_all(out.as_bytes()).map_err(|err| err.to_string())
+    }
+}
+
+/// Indent is either a number of spaces, or "tab" in configuration files.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Indent {
+    Spaces(u8),
+    Tab,
+}
+
+impl Default for Indent {
+    fn default() -> Self {
+        Indent::Spaces(2)
+    }
+}
+
+impl Indent {
+    /// Returns the string that indents a single level.
+    pub fn to_str(self) -> String {
+        match self {
+            Indent::Spaces(n) => " ".repeat(n.into()),
+            Indent::Tab => "\t".to_string(),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Indent {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Spaces(u8),
+            Name(String),
+        }
+        match Raw::deserialize(deserializer)? {
+            Raw::Spaces(n) => Ok(Indent::Spaces(n)),
+            Raw::Name(name) if name == "tab" => Ok(Indent::Tab),
+            Raw::Name(name) => Err(serde::de::Error::custom(format!(
+                "invalid indent \"{name}\", expected a number of spaces or \"tab\""
+            ))),
+        }
+    }
+}
+
+/// Locator finds the line and column of increasing byte offsets in a
+/// document, without rescanning it from the start for each one.
+#[cfg(any(
+    feature = "json",
+    feature = "json5",
+    feature = "markdown",
+    feature = "sql",
+    feature = "xml"
+))]
+struct Locator<'a> {
+    input: &'a str,
+    line: usize,
+    line_start: usize,
+    pos: usize,
+}
+
+#[cfg(any(
+    feature = "json",
+    feature = "json5",
+    feature = "markdown",
+    feature = "sql",
+    feature = "xml"
+))]
+impl<'a> Locator<'a> {
+    /// Creates a Locator whose first line is numbered `first_line`.
+    fn new(input: &'a str, first_line: usize) -> Self {
+        Locator {
+            input,
+            line: first_line,
+            line_start: 0,
+            pos: 0,
+        }
+    }
+
+    /// Returns the line and 1-based column of the offset, which must not be
+    /// before the previous one.
+    fn locate(&mut self, offset: usize) -> (usize, usize) {
+        for (i, _) in self.input[self.pos..offset].match_indices('\n') {
+            self.line += 1;
+            self.line_start = self.pos + i + 1;
+        }
+        self.pos = offset;
+        let column = self.input[self.line_start..offset].chars().count() + 1;
+        (self.line, column)
+    }
+}
+
+/// Adds the offset to every line number in the message, e.g. "line 3", for
+/// content that starts after the first line of a document.
+#[cfg(any(feature = "markdown", feature = "yaml"))]
+fn shift_lines(message: &str, offset: usize) -> String {
+    let mut out = String::with_capacity(message.len());
+    let mut rest = message;
+    while let Some(i) = rest.find("line ") {
+        let (before, after) = rest.split_at(i + "line ".len());
+        out.push_str(before);
+        let digits = after.len() - after.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        match after[..digits].parse::<usize>() {
+            Ok(line) => out.push_str(&(line + offset).to_string()),
+            Err(_) => out.push_str(&after[..digits]),
+        }
+        rest = &after[digits..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Kind represents a type of file that metafmt knows how to format.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Dotenv,
+    Ini,
+    Json,
+    Json5,
+    Jsonl,
+    Markdown,
+    Sql,
+    Toml,
+    Xml,
+    Yaml,
+}
+
+impl Kind {
+    /// Returns the kind associated with the provided file extension.
+    pub fn from_extension(ext: &str) -> Option<Self> {
+        match ext {
+            "env" => Some(Kind::Dotenv),
+            "ini" | "cfg" | "properties" | "editorconfig" | "gitconfig" | "gitmodules" => {
+                Some(Kind::Ini)
+            }
+            "json" | "jsonc" | "hjson" | "jwcc" => Some(Kind::Json),
+            "json5" => Some(Kind::Json5),
+            "jsonl" | "ndjson" => Some(Kind::Jsonl),
+            "md" => Some(Kind::Markdown),
+            "sql" => Some(Kind::Sql),
+            "toml" => Some(Kind::Toml),
+            "xml" | "svg" | "csproj" | "props" | "targets" => Some(Kind::Xml),
+            "yaml" | "yml" => Some(Kind::Yaml),
+            _ => None,
+        }
+    }
+
+    /// Returns the name of the kind, e.g. "yaml".
+    pub fn name(&self) -> &'static str {
+        match self {
+            Kind::Dotenv => "dotenv",
+            Kind::Ini => "ini",
+            Kind::Json => "json",
+            Kind::Json5 => "json5",
+            Kind::Jsonl => "jsonl",
+            Kind::Markdown => "markdown",
+            Kind::Sql => "sql",
+            Kind::Toml => "toml",
+            Kind::Xml => "xml",
+            Kind::Yaml => "yaml",
+        }
+    }
+
+    /// Returns true if the kind's cargo feature is enabled.
+    pub fn is_available(&self) -> bool {
+        match self {
+            Kind::Dotenv => cfg!(feature = "dotenv"),
+            Kind::Ini => cfg!(feature = "ini"),
+            Kind::Json => cfg!(feature = "json"),
+            Kind::Json5 => cfg!(feature = "json5"),
+            Kind::Jsonl => cfg!(feature = "jsonl"),
+            Kind::Markdown => cfg!(feature = "markdown"),
+            Kind::Sql => cfg!(feature = "sql"),
+            Kind::Toml => cfg!(feature = "toml"),
+            Kind::Xml => cfg!(feature = "xml"),
+            Kind::Yaml => cfg!(feature = "yaml"),
+        }
+    }
+
+    /// Returns the kind associated with the provided path's extension.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        extension(path).and_then(Self::from_extension)
+    }
+
+    /// Returns the kind associated with a filetype name, such as the value
+    /// provided to '--stdin-filetype' or an editor's language identifier.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "dotenv" => Some(Kind::Dotenv),
+            "markdown" => Some(Kind::Markdown),
+            name => Self::from_extension(name),
+        }
+    }
+}
+
+/// The suffixes of .env file variants, such as .env.example or
+/// .env.development.local. Other files, such as .env.schema.json, are
+/// matched by their extension.
+const DOTENV_SUFFIXES: &[&str] = &[
+    "ci",
+    "defaults",
+    "dev",
+    "development",
+    "dist",
+    "example",
+    "local",
+    "prod",
+    "production",
+    "sample",
+    "staging",
+    "template",
+    "test",
+    "testing",
+];
+
+/// Returns the extension of the path. Dotfiles without an extension, such as
+/// .editorconfig, are matched by their name without the leading dot, and
+/// variants of .env files, such as .env.example, as "env".
+fn extension(path: &Path) -> Option<&str> {
+    let name = path.file_name()?.to_str()?;
+    let is_dotenv = name
+        .strip_prefix(".env.")
+        .is_some_and(|suffix| suffix.split('.').all(|s| DOTENV_SUFFIXES.contains(&s)));
+    if is_dotenv {
+        return Some("env");
+    }
+    match path.extension() {
+        Some(ext) => ext.to_str(),
+        None => name.strip_prefix('.'),
+    }
+}
+
+/// Returns a stand-in path for content of the provided filetype name that
+/// isn't read from a file, such as stdin. Formatters that depend on a file's
+/// extension (e.g. strict .json files) then treat the content the same way.
+pub fn path_for_name(name: &str) -> PathBuf {
+    Path::new("-").with_extension(name)
+}
+
+/// Formatters holds an instance of every formatter, keyed by Kind, along with
+/// any configured external formatters.
+///
+/// When deserialized, each field holds the options for its formatter, e.g.
+/// `{"yaml": {}}`.
+#[derive(Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Formatters {
+    pub dotenv: Dotenv,
+    pub ini: Ini,
+    pub json: Json,
+    pub json5: Json5,
+    pub jsonl: Jsonl,
+    pub markdown: Markdown,
+    pub sql: Sql,
+    pub toml: Toml,
+    pub xml: Xml,
+    pub yaml: Yaml,
+    pub external: Vec<External>,
+}
+
+impl Formatters {
+    /// Returns the formatter for the provided kind. If the kind's cargo
+    /// feature is disabled, the returned formatter always returns an error.
+    pub fn get(&self, kind: Kind) -> &dyn Format {
+        match kind {
+            #[cfg(feature = "dotenv")]
+            Kind::Dotenv => &self.dotenv,
+            #[cfg(feature = "ini")]
+            Kind::Ini => &self.ini,
+            #[cfg(feature = "json")]
+            Kind::Json => &self.json,
+            #[cfg(feature = "json5")]
+            Kind::Json5 => &self.json5,
+            #[cfg(feature = "jsonl")]
+            Kind::Jsonl => &self.jsonl,
+            #[cfg(feature = "markdown")]
+            Kind::Markdown => &self.markdown,
+            #[cfg(feature = "sql")]
+            Kind::Sql => &self.sql,
+            #[cfg(feature = "toml")]
+            Kind::Toml => &self.toml,
+            #[cfg(feature = "xml")]
+            Kind::Xml => &self.xml,
+            #[cfg(feature = "yaml")]
+            Kind::Yaml => &self.yaml,
+            #[allow(unreachable_patterns)]
+            kind => Unavailable::get(kind),
+        }
+    }
+
+    /// Returns the formatter for the provided file extension. External
+    /// formatters take precedence over the built-in ones.
+    pub fn for_extension(&self, ext: &str) -> Option<&dyn Format> {
+        match self.external.iter().find(|v| v.handles(ext)) {
+            Some(external) => Some(external),
+            None => Kind::from_extension(ext).map(|kind| self.get(kind)),
+        }
+    }
+
+    /// Returns the formatter for the provided path's extension. None is
+    /// returned for formats whose cargo feature is disabled, so that their
+    /// files are skipped rather than reported.
+    pub fn for_path(&self, path: &Path) -> Option<&dyn Format> {
+        extension(path).and_then(|ext| self.for_embedded(ext))
+    }
+
+    /// Returns the formatter for a filetype name, such as the value provided
+    /// to '--stdin-filetype' or an editor's language identifier.
+    pub fn for_name(&self, name: &str) -> Option<&dyn Format> {
+        match self.external.iter().find(|v| v.handles(name)) {
+            Some(external) => Some(external),
+            None => Kind::from_name(name).map(|kind| self.get(kind)),
+        }
+    }
+
+    /// Returns the formatter for a language embedded in another format, such
+    /// as a code block in Markdown, or for an editor's language identifier.
+    /// Unlike `for_name`, None is returned for formats whose cargo feature is
+    /// disabled.
+    pub fn for_embedded(&self, name: &str) -> Option<&dyn Format> {
+        match self.external.iter().find(|v| v.handles(name)) {
+            Some(external) => Some(external),
+            None => Kind::from_name(name)
+                .filter(Kind::is_available)
+                .map(|kind| self.get(kind)),
+        }
+    }
+
+    /// Returns the formatters, with those that embed other formats (e.g. code
+    /// blocks in Markdown) formatting them using a copy of the others. This
+    /// is done after deserializing, so that embedded code is formatted with
+    /// the same configuration as files of its format. JSON5 files also take
+    /// their layout from the `json` options here.
+    #[cfg_attr(
+        not(any(
+            all(feature = "json", feature = "json5"),
+            feature = "markdown-fences",
+            feature = "yaml"
+        )),
+        allow(unused_mut)
+    )]
+    pub fn link(mut self) -> Self {
+        #[cfg(all(feature = "json", feature = "json5"))]
+        self.json5
+            .set_layout(self.json.indent, self.json.line_width);
+        #[cfg(any(feature = "markdown-fences", feature = "yaml"))]
+        {
+            let linked = Arc::new(self.clone());
+            #[cfg(feature = "markdown-fences")]
+            self.markdown.set_formatters(Arc::clone(&linked));
+            #[cfg(feature = "yaml")]
+            self.yaml.set_formatters(linked);
+        }
+        self
+    }
+}
+
+/// Calls `f` with the linked formatters, or the default ones for a formatter
+/// that wasn't created from a configuration. See `Formatters::link`.
+#[cfg(any(feature = "markdown-fences", feature = "yaml"))]
+fn with_linked<T>(linked: Option<&Formatters>, f: impl FnOnce(&Formatters) -> T) -> T {
+    match linked {
+        Some(formatters) => f(formatters),
+        None => f(&Formatters::default()),
+    }
+}
+
+/// Unavailable stands in for a formatter whose cargo feature is disabled.
+struct Unavailable(Kind);
+
+impl Unavailable {
+    #[allow(dead_code)]
+    fn get(kind: Kind) -> &'static dyn Format {
+        match kind {
+            Kind::Dotenv => &Unavailable(Kind::Dotenv),
+            Kind::Ini => &Unavailable(Kind::Ini),
+            Kind::Json => &Unavailable(Kind::Json),
+            Kind::Json5 => &Unavailable(Kind::Json5),
+            Kind::Jsonl => &Unavailable(Kind::Jsonl),
+            Kind::Markdown => &Unavailable(Kind::Markdown),
+            Kind::Sql => &Unavailable(Kind::Sql),
+            Kind::Toml => &Unavailable(Kind::Toml),
+            Kind::Xml => &Unavailable(Kind::Xml),
+            Kind::Yaml => &Unavailable(Kind::Yaml),
+        }
+    }
+}
+
+impl Format for Unavailable {
+    fn format(&self, _input: &str) -> Result<String, String> {
+        Err(format!(
+            "{} support is not compiled in (enable the '{}' cargo feature)",
+            self.0.name(),
+            self.0.name()
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(any(
+        feature = "json",
+        feature = "json5",
+        feature = "markdown",
+        feature = "sql",
+        feature = "xml"
+    ))]
+    fn test_locator() {
+        let mut locator = Locator::new("ab\ncdé\n\nf", 1);
+        assert_eq!(locator.locate(1), (1, 2));
+        assert_eq!(locator.locate(3), (2, 1));
+        assert_eq!(locator.locate(7), (2, 4));
+        assert_eq!(locator.locate(9), (4, 1));
+    }
+
+    #[test]
+    #[cfg(any(feature = "markdown", feature = "yaml"))]
+    fn test_shift_lines() {
+        assert_eq!(
+            shift_lines("error at line 3 column 2 (line 10, inline)", 2),
+            "error at line 5 column 2 (line 12, inline)"
+        );
+    }
+
+    #[test]
+    fn test_for_embedded() {
+        let formatters = Formatters {
+            external: vec![External {
+                extensions: vec!["diff".to_string()],
+                command: "cat".to_string(),
+                ..External::default()
+            }],
+            ..Formatters::default()
+        };
+        assert!(formatters.for_embedded("diff").is_some());
+        assert!(formatters.for_embedded("css").is_none());
+        assert_eq!(
+            formatters.for_embedded("yml").is_some(),
+            cfg!(feature = "yaml")
+        );
+        assert!(formatters.for_name("yml").is_some());
+    }
+
+    #[test]
+    fn test_for_path() {
+        let formatters = Formatters::default();
+        assert_eq!(
+            formatters.for_path(Path::new("a/b.toml")).is_some(),
+            cfg!(feature = "toml")
+        );
+        assert_eq!(
+            formatters.for_path(Path::new(".env.local")).is_some(),
+            cfg!(feature = "dotenv")
+        );
+        assert!(formatters.for_path(Path::new("Makefile")).is_none());
+        assert!(formatters.for_name("toml").is_some());
+    }
+
+    #[test]
+    fn test_from_path() {
+        assert_eq!(Kind::from_path(Path::new("a/b.yml")), Some(Kind::Yaml));
+        assert_eq!(
+            Kind::from_path(Path::new("a/.editorconfig")),
+            Some(Kind::Ini)
+        );
+        assert_eq!(Kind::from_path(Path::new(".config.toml")), Some(Kind::Toml));
+        assert_eq!(Kind::from_path(Path::new(".env")), Some(Kind::Dotenv));
+        assert_eq!(
+            Kind::from_path(Path::new(".env.example")),
+            Some(Kind::Dotenv)
+        );
+        assert_eq!(Kind::from_path(Path::new("prod.env")), Some(Kind::Dotenv));
+        assert_eq!(
+            Kind::from_path(Path::new(".env.development.local")),
+            Some(Kind::Dotenv)
+        );
+        assert_eq!(
+            Kind::from_path(Path::new(".env.schema.json")),
+            Some(Kind::Json)
+        );
+        assert_eq!(
+            Kind::from_path(Path::new(".env.production.yaml")),
+            Some(Kind::Yaml)
+        );
+        assert_eq!(Kind::from_path(Path::new(".env.bak")), None);
+        assert_eq!(Kind::from_path(Path::new("Makefile")), None);
+        assert_eq!(Kind::from_path(Path::new(".gitignore")), None);
+    }
 }
@@ -1,18 +1,825 @@
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use serde::Deserialize;
 use sqlformat::{format, FormatOptions, QueryParams};
 
-use super::Format;
+use super::{Format, Indent, Locator};
+
+/// Sql formats SQL queries.
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Sql {
+    /// The dialect that determines how brackets and placeholders are read.
+    pub dialect: Dialect,
+    /// The case that reserved keywords are written in.
+    pub keyword_case: KeywordCase,
+    /// The indentation of nested clauses.
+    pub indent: Indent,
+    /// The number of line breaks after each query.
+    pub lines_between_queries: u8,
+    /// The maximum length of a parenthesized block written on one line.
+    pub max_inline_block: usize,
+    /// The maximum length of a list of arguments written on one line. Each
+    /// argument is written on its own line if unset.
+    pub max_inline_arguments: Option<usize>,
+    /// The maximum length of a top-level clause's arguments written on the
+    /// same line as the clause.
+    pub max_inline_top_level: Option<usize>,
+    /// How input that can't be formatted correctly, such as an unterminated
+    /// string or unbalanced parentheses, is reported.
+    pub validate: Validate,
+    /// The template language that SQL files are written in.
+    pub templating: Templating,
+
+    /// Whether each directory that was looked up is part of a dbt project,
+    /// shared by every file formatted with this configuration.
+    #[serde(skip)]
+    dbt_dirs: Arc<Mutex<HashMap<PathBuf, bool>>>,
+}
+
+impl Default for Sql {
+    fn default() -> Self {
+        Sql {
+            dialect: Dialect::default(),
+            keyword_case: KeywordCase::default(),
+            indent: Indent::default(),
+            lines_between_queries: 1,
+            max_inline_block: 50,
+            max_inline_arguments: None,
+            max_inline_top_level: None,
+            validate: Validate::default(),
+            templating: Templating::default(),
+            dbt_dirs: Arc::default(),
+        }
+    }
+}
+
+/// Dialect selects the SQL syntax that is recognized.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Dialect {
+    #[default]
+    Generic,
+    /// Recognizes array brackets, and `$1` placeholders before named ones.
+    #[serde(alias = "postgres")]
+    Postgresql,
+    /// Recognizes `[bracketed identifiers]`, and `@name` placeholders before
+    /// indexed ones.
+    #[serde(alias = "tsql")]
+    Sqlserver,
+    /// Recognizes `#` comments.
+    #[serde(alias = "mariadb")]
+    Mysql,
+}
+
+impl Dialect {
+    fn options(self) -> (sqlformat::Dialect, QueryParams) {
+        // Placeholders are never replaced, as no values are provided; the
+        // kind of parameters only changes which placeholders take precedence.
+        match self {
+            Dialect::Generic | Dialect::Mysql => (sqlformat::Dialect::Generic, QueryParams::None),
+            Dialect::Postgresql => (
+                sqlformat::Dialect::PostgreSql,
+                QueryParams::Indexed(Vec::new()),
+            ),
+            Dialect::Sqlserver => (
+                sqlformat::Dialect::SQLServer,
+                QueryParams::Named(Vec::new()),
+            ),
+        }
+    }
+}
 
-#[derive(Clone, Copy, Default)]
-pub(crate) struct Sql {}
+/// KeywordCase controls the case of reserved keywords.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum KeywordCase {
+    #[default]
+    Upper,
+    Lower,
+    Preserve,
+}
+
+/// Validate controls how problems found in the input are reported.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Validate {
+    /// Problems are errors, and the file is not formatted.
+    #[default]
+    Error,
+    /// Problems are reported as warnings, and the file is formatted.
+    Warn,
+    /// The input is not checked.
+    Off,
+}
+
+/// Templating selects the template language of SQL files, such as the
+/// Jinja used by dbt models.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Templating {
+    /// Jinja is used for files in a dbt project, i.e. a directory containing
+    /// a dbt_project.yml file or any of its subdirectories.
+    #[default]
+    Auto,
+    Jinja,
+    Off,
+}
 
 impl Format for Sql {
     fn format(&self, input: &str) -> Result<String, String> {
+        self.format_sql(input, self.is_templated(None))
+    }
+
+    fn format_file(&self, path: &Path, input: &str) -> Result<String, String> {
+        self.format_sql(input, self.is_templated(Some(path)))
+    }
+
+    fn supports_range(&self) -> bool {
+        true
+    }
+
+    fn warnings(&self, path: Option<&Path>, input: &str) -> Vec<String> {
+        if self.validate != Validate::Warn {
+            return Vec::new();
+        }
+        let template = self.is_templated(path).then(|| Template::mask(input));
+        let input = template.as_ref().map_or(input, |t| &t.masked);
+        validate(input, &scan(input, self.dialect))
+    }
+}
+
+impl Sql {
+    fn is_templated(&self, path: Option<&Path>) -> bool {
+        match self.templating {
+            Templating::Auto => path.is_some_and(|path| self.in_dbt_project(path)),
+            Templating::Jinja => true,
+            Templating::Off => false,
+        }
+    }
+
+    /// Returns true if the file is part of a dbt project.
+    fn in_dbt_project(&self, path: &Path) -> bool {
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let dir = std::path::absolute(dir).unwrap_or_else(|_| dir.to_path_buf());
+        self.in_dbt_dir(&dir)
+    }
+
+    /// Returns true if the directory or any of its parents contains a
+    /// dbt_project.yml file, caching the result for every directory visited.
+    fn in_dbt_dir(&self, dir: &Path) -> bool {
+        if let Some(&found) = self.dbt_dirs.lock().unwrap().get(dir) {
+            return found;
+        }
+
+        let found = dir.join("dbt_project.yml").is_file()
+            || dir.parent().is_some_and(|parent| self.in_dbt_dir(parent));
+
+        self.dbt_dirs
+            .lock()
+            .unwrap()
+            .insert(dir.to_path_buf(), found);
+        found
+    }
+
+    fn format_sql(&self, input: &str, templated: bool) -> Result<String, String> {
+        // Template tags are replaced by placeholders that sqlformat leaves in
+        // place, and are restored once the SQL around them is formatted.
+        let template = templated.then(|| Template::mask(input));
+        let input = template.as_ref().map_or(input, |t| &t.masked);
+
+        let (dialect, params) = self.dialect.options();
         let opts = FormatOptions {
-            uppercase: Some(true),
+            indent: match self.indent {
+                Indent::Spaces(n) => sqlformat::Indent::Spaces(n),
+                Indent::Tab => sqlformat::Indent::Tabs,
+            },
+            uppercase: match self.keyword_case {
+                KeywordCase::Upper => Some(true),
+                KeywordCase::Lower => Some(false),
+                KeywordCase::Preserve => None,
+            },
+            lines_between_queries: self.lines_between_queries,
+            max_inline_block: self.max_inline_block,
+            max_inline_arguments: self.max_inline_arguments,
+            max_inline_top_level: self.max_inline_top_level,
+            dialect,
             ..FormatOptions::default()
         };
-        let mut out = format(input, &QueryParams::None, &opts);
+        let tokens = scan(input, self.dialect);
+        if self.validate == Validate::Error {
+            let errors = validate(input, &tokens);
+            if !errors.is_empty() {
+                return Err(errors.join("\n"));
+            }
+        }
+
+        // Dollar-quoted strings aren't recognized by sqlformat, so their
+        // contents are replaced by ordinary strings while formatting.
+        let (input, bodies) = match self.dialect {
+            Dialect::Postgresql => mask_dollar_quotes(input, &tokens),
+            _ => (input.into(), Vec::new()),
+        };
+        let mut out = format(&input, &params, &opts);
+        for (i, body) in bodies.iter().enumerate() {
+            out = out.replacen(&mask(i), body, 1);
+        }
+        if let Some(template) = template {
+            out = template.restore(&out, &self.indent.to_str());
+        }
         out.push('\n');
         Ok(out)
     }
 }
+
+/// Returns the string literal that stands in for the dollar-quoted string
+/// with the provided index.
+fn mask(i: usize) -> String {
+    format!("'\u{E000}{i}'")
+}
+
+/// Replaces every dollar-quoted string (e.g. `$$ ... $$` or `$body$ ... $body$`)
+/// with a string literal, returning the original strings in order.
+fn mask_dollar_quotes<'a>(input: &'a str, tokens: &[Token]) -> (Cow<'a, str>, Vec<&'a str>) {
+    let mut bodies = Vec::new();
+    let mut out = String::new();
+    let mut copied = 0;
+    for token in tokens.iter().filter(|t| t.kind == TokenKind::DollarQuoted) {
+        out.push_str(&input[copied..token.start]);
+        out.push_str(&mask(bodies.len()));
+        bodies.push(&input[token.start..token.end]);
+        copied = token.end;
+    }
+    if bodies.is_empty() {
+        return (Cow::Borrowed(input), bodies);
+    }
+    out.push_str(&input[copied..]);
+    (Cow::Owned(out), bodies)
+}
+
+// --- Templates ---
+
+/// The start of the placeholders that replace template tags.
+const TAG_PLACEHOLDER: &str = "_jinja";
+
+/// Template holds SQL whose Jinja tags ({{ ... }}, {% ... %} and {# ... #})
+/// have been replaced by placeholders. Tags on a line of their own become line
+/// comments, which sqlformat keeps on their own line, and other tags become
+/// identifiers of the same length.
+struct Template<'a> {
+    masked: String,
+    tags: Vec<Tag<'a>>,
+}
+
+struct Tag<'a> {
+    text: &'a str,
+    placeholder: String,
+    own_line: bool,
+}
+
+/// Block describes how a tag affects the indentation of the lines after it.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Block {
+    Open,
+    Middle,
+    Close,
+    None,
+}
+
+impl<'a> Template<'a> {
+    fn mask(input: &'a str) -> Self {
+        let mut masked = String::with_capacity(input.len());
+        let mut tags = Vec::new();
+        let mut pos = 0;
+        while let Some(i) = input[pos..].find('{') {
+            let start = pos + i;
+            let close = match input[start + 1..].chars().next() {
+                Some('{') => "}}",
+                Some('%') => "%}",
+                Some('#') => "#}",
+                _ => {
+                    masked.push_str(&input[pos..start + 1]);
+                    pos = start + 1;
+                    continue;
+                }
+            };
+            let Some(len) = input[start + 2..].find(close) else {
+                break;
+            };
+            let mut end = start + len + 4;
+            let line_start = input[..start].rfind('\n').map_or(0, |i| i + 1);
+            let line_end = input[end..].find('\n').map_or(input.len(), |i| end + i);
+            // A line of several tags, such as {% if x %},{% endif %}, is kept
+            // as written, since sqlformat would separate them.
+            let rest = input[end..line_end].trim_end();
+            if input[line_start..start].trim().is_empty()
+                && rest.contains('{')
+                && ["}}", "%}", "#}"].iter().any(|v| rest.ends_with(v))
+            {
+                end += rest.len();
+            }
+            let text = &input[start..end];
+            masked.push_str(&input[pos..start]);
+            // Expressions within a statement remain part of it, even when
+            // they are on a line of their own.
+            let own_line = input[line_start..start].trim().is_empty()
+                && input[end..line_end].trim().is_empty()
+                && (close != "}}" || at_statement_start(&masked));
+            let placeholder = match own_line {
+                true => format!("-- {TAG_PLACEHOLDER}{}", tags.len()),
+                false => {
+                    let placeholder = format!("{TAG_PLACEHOLDER}{}_", tags.len());
+                    let width = text.lines().next().unwrap_or_default().len();
+                    format!("{placeholder:_<width$}")
+                }
+            };
+            masked.push_str(&placeholder);
+            // Line breaks are kept, so that errors refer to the right line.
+            masked.extend(text.matches('\n').map(|_| '\n'));
+            tags.push(Tag {
+                text,
+                placeholder,
+                own_line,
+            });
+            pos = end;
+        }
+        masked.push_str(&input[pos..]);
+        Template { masked, tags }
+    }
+
+    /// Replaces the placeholders in the formatted SQL with their tags,
+    /// indenting the lines within blocks such as {% if %} and {% for %}.
+    fn restore(&self, formatted: &str, unit: &str) -> String {
+        let lines: Vec<&str> = formatted.split('\n').collect();
+        let tag_at = |line: &str| {
+            let trimmed = line.trim();
+            self.tags
+                .iter()
+                .find(|tag| tag.own_line && tag.placeholder == trimmed)
+        };
+
+        let mut out = String::with_capacity(formatted.len());
+        let mut blocks: Vec<String> = Vec::new();
+        for (n, line) in lines.iter().enumerate() {
+            if n > 0 {
+                out.push('\n');
+            }
+            let Some(tag) = tag_at(line) else {
+                if !line.trim().is_empty() {
+                    out.push_str(&unit.repeat(blocks.len()));
+                }
+                out.push_str(line);
+                continue;
+            };
+
+            // Tags are indented like the SQL that follows them, or the SQL
+            // before them at the end of the input.
+            let is_sql = |line: &&&str| tag_at(line).is_none() && !line.trim().is_empty();
+            let next = lines[n + 1..]
+                .iter()
+                .find(is_sql)
+                .or_else(|| lines[..n].iter().rev().find(is_sql))
+                .map_or("", |line| &line[..line.len() - line.trim_start().len()]);
+            let indent = format!("{next}{}", unit.repeat(blocks.len()));
+            let indent = match block_of(tag.text) {
+                Block::Open => {
+                    blocks.push(indent.clone());
+                    indent
+                }
+                Block::Middle => blocks.last().cloned().unwrap_or(indent),
+                Block::Close => blocks.pop().unwrap_or(indent),
+                Block::None => indent,
+            };
+            out.push_str(&indent);
+            out.push_str(tag.text);
+        }
+
+        for tag in &self.tags {
+            out = out.replacen(&tag.placeholder, tag.text, 1);
+        }
+        out
+    }
+}
+
+/// Returns true if the SQL ends with a complete statement, ignoring any
+/// comments after it.
+fn at_statement_start(sql: &str) -> bool {
+    sql.lines()
+        .rev()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with("--"))
+        .is_none_or(|line| line.ends_with(';'))
+}
+
+/// Returns the effect of a line of one or more tags on the indentation of the
+/// lines after it.
+fn block_of(text: &str) -> Block {
+    let blocks: Vec<Block> = text
+        .match_indices("{%")
+        .map(|(i, _)| block_of_tag(&text[i..]))
+        .filter(|block| *block != Block::None)
+        .collect();
+    let depth: isize = blocks
+        .iter()
+        .map(|block| match block {
+            Block::Open => 1,
+            Block::Close => -1,
+            _ => 0,
+        })
+        .sum();
+    match (blocks.first(), blocks.last()) {
+        _ if depth > 0 => Block::Open,
+        _ if depth < 0 => Block::Close,
+        (Some(Block::Middle), _) | (Some(Block::Close), Some(Block::Open)) => Block::Middle,
+        _ => Block::None,
+    }
+}
+
+/// Returns the effect of the tag at the start of the text on the indentation
+/// of the lines after it.
+fn block_of_tag(tag: &str) -> Block {
+    let Some(inner) = tag.strip_prefix("{%") else {
+        return Block::None;
+    };
+    let inner = inner.trim_start_matches(['-', '+']).trim_start();
+    let name = inner
+        .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .next()
+        .unwrap_or_default();
+    match name {
+        "block" | "call" | "docs" | "filter" | "for" | "if" | "macro" | "materialization"
+        | "raw" | "snapshot" | "test" | "with" => Block::Open,
+        // {% set x %}...{% endset %} captures a block, unlike {% set x = 1 %}.
+        "set" if !inner.contains('=') => Block::Open,
+        "elif" | "else" => Block::Middle,
+        name if name.starts_with("end") => Block::Close,
+        _ => Block::None,
+    }
+}
+
+// --- Validation ---
+
+/// The keywords that a statement may start with, separated by whitespace.
+const STATEMENT_KEYWORDS: &str = "\
+    ABORT ALTER ANALYSE ANALYZE ATTACH BEGIN CALL CASE CHECKPOINT CLOSE CLUSTER COMMENT COMMIT \
+    CONTINUE COPY CREATE DEALLOCATE DECLARE DELETE DELIMITER DESC DESCRIBE DETACH DISCARD DO \
+    DROP ELSE ELSEIF ELSIF END EXEC EXECUTE EXIT EXPLAIN FETCH FOR FOREACH FROM GET GO GOTO \
+    GRANT HANDLER IF IMPORT INSERT INSTALL ITERATE KILL LEAVE LISTEN LOAD LOCK LOOP MERGE MOVE \
+    NOTIFY OPEN OPTIMIZE PERFORM PRAGMA PREPARE PRINT PUT RAISE REFRESH REINDEX RELEASE RENAME \
+    REPAIR REPEAT REPLACE RESET RESIGNAL RETURN REVOKE ROLLBACK SAVEPOINT SELECT SET SHOW \
+    SIGNAL START TABLE THROW TRUNCATE UNINSTALL UNLISTEN UNLOAD UNLOCK UNTIL UPDATE UPSERT USE \
+    VACUUM VALUES WHEN WHILE WITH";
+
+/// Returns a message for every problem that would prevent the input from
+/// being formatted correctly: unterminated strings and comments, unbalanced
+/// parentheses, and statements that don't start with a keyword.
+fn validate(input: &str, tokens: &[Token]) -> Vec<String> {
+    let mut errors = Vec::new();
+    let mut locator = Locator::new(input, 1);
+    let mut parens = Vec::new();
+    let mut statement_start = true;
+    for (i, token) in tokens.iter().enumerate() {
+        let (line, column) = locator.locate(token.start);
+        let text = &input[token.start..token.end];
+        if !token.terminated {
+            let what = match token.kind {
+                TokenKind::Comment => "comment",
+                TokenKind::DollarQuoted => "dollar-quoted string",
+                _ if text.starts_with('\'') => "string",
+                _ => "quoted identifier",
+            };
+            errors.push(format!(
+                "unterminated {what} at line {line} column {column}"
+            ));
+            continue;
+        }
+        match token.kind {
+            TokenKind::Comment => continue,
+            TokenKind::Punct('(') => parens.push((line, column)),
+            TokenKind::Punct(')') => match parens.pop() {
+                Some(_) => {}
+                None => errors.push(format!("unmatched ')' at line {line} column {column}")),
+            },
+            TokenKind::Punct(';') if parens.is_empty() => {
+                statement_start = true;
+                continue;
+            }
+            // Labels, such as "retry:", come before a statement.
+            TokenKind::Word
+                if statement_start
+                    && tokens
+                        .get(i + 1)
+                        .is_some_and(|t| t.kind == TokenKind::Punct(':')) =>
+            {
+                continue;
+            }
+            TokenKind::Punct(':') if statement_start => continue,
+            TokenKind::Word
+                if statement_start
+                    && !is_statement_keyword(text)
+                    && !text.starts_with(TAG_PLACEHOLDER) =>
+            {
+                errors.push(format!(
+                    "unrecognized statement \"{text}\" at line {line} column {column}"
+                ));
+            }
+            TokenKind::Word => {}
+            _ if statement_start => {
+                errors.push(format!(
+                    "unexpected \"{text}\" at the start of a statement at line {line} column {column}"
+                ));
+            }
+            _ => {}
+        }
+        statement_start = false;
+    }
+    for (line, column) in parens {
+        errors.push(format!("unclosed '(' at line {line} column {column}"));
+    }
+    errors
+}
+
+fn is_statement_keyword(word: &str) -> bool {
+    STATEMENT_KEYWORDS
+        .split_whitespace()
+        .any(|keyword| keyword.eq_ignore_ascii_case(word))
+}
+
+// --- Scanner ---
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum TokenKind {
+    Word,
+    /// A string, or a quoted identifier.
+    Quoted,
+    DollarQuoted,
+    Comment,
+    Punct(char),
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Token {
+    kind: TokenKind,
+    start: usize,
+    end: usize,
+    /// False for strings and comments that continue to the end of the input.
+    terminated: bool,
+}
+
+/// Splits the input into the tokens that determine the structure of its
+/// statements, the same way that sqlformat does.
+fn scan(input: &str, dialect: Dialect) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+    while let Some(c) = input[pos..].chars().next() {
+        let rest = &input[pos..];
+        let line_len = rest.find('\n').unwrap_or(rest.len());
+        let (kind, len) = match c {
+            c if c.is_whitespace() => {
+                pos += c.len_utf8();
+                continue;
+            }
+            '\'' | '"' => (TokenKind::Quoted, quoted_len(rest, c, true)),
+            '`' => (TokenKind::Quoted, quoted_len(rest, c, false)),
+            '[' if dialect == Dialect::Sqlserver => {
+                (TokenKind::Quoted, quoted_len(rest, ']', false))
+            }
+            '#' if dialect == Dialect::Mysql => (TokenKind::Comment, Some(line_len)),
+            '-' if rest.starts_with("--") => (TokenKind::Comment, Some(line_len)),
+            // psql meta-commands, such as \echo, take up the rest of the line.
+            '\\' if input[..pos].trim_end_matches([' ', '\t']).ends_with('\n') || pos == 0 => {
+                (TokenKind::Comment, Some(line_len))
+            }
+            '/' if rest.starts_with("/*") => {
+                (TokenKind::Comment, rest[2..].find("*/").map(|i| i + 4))
+            }
+            '$' => match dollar_tag(rest) {
+                Some(tag) => (
+                    TokenKind::DollarQuoted,
+                    rest[tag.len()..].find(tag).map(|i| i + 2 * tag.len()),
+                ),
+                None => (TokenKind::Word, Some(word_len(rest))),
+            },
+            c if is_word_char(c) => (TokenKind::Word, Some(word_len(rest))),
+            c => (TokenKind::Punct(c), Some(c.len_utf8())),
+        };
+        let end = pos + len.unwrap_or(rest.len());
+        tokens.push(Token {
+            kind,
+            start: pos,
+            end,
+            terminated: len.is_some(),
+        });
+        pos = end;
+    }
+    tokens
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '@' | '$' | '.')
+}
+
+fn word_len(input: &str) -> usize {
+    input.find(|c| !is_word_char(c)).unwrap_or(input.len())
+}
+
+/// Returns the length of the string or quoted identifier at the start of the
+/// input, where a doubled closing quote is an escaped one, or None if it
+/// isn't terminated.
+fn quoted_len(input: &str, close: char, backslash_escapes: bool) -> Option<usize> {
+    let mut chars = input.char_indices().skip(1).peekable();
+    while let Some((i, c)) = chars.next() {
+        if c == '\\' && backslash_escapes {
+            chars.next();
+        } else if c == close && chars.next_if(|(_, c)| *c == close).is_none() {
+            return Some(i + 1);
+        }
+    }
+    None
+}
+
+/// Returns the opening delimiter of the dollar-quoted string at the start of
+/// the input, such as `$$` or `$body$`.
+fn dollar_tag(input: &str) -> Option<&str> {
+    let end = input[1..].find(|c: char| !(c.is_alphanumeric() || c == '_'))? + 1;
+    let tag = &input[1..end];
+    let valid = !tag.starts_with(|c: char| c.is_ascii_digit()) && input[end..].starts_with('$');
+    valid.then(|| &input[..end + 1])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keyword_case() {
+        let input = "Select a, B from t";
+        let sql = |keyword_case| Sql {
+            keyword_case,
+            ..Sql::default()
+        };
+        let format = |case| sql(case).format(input).unwrap();
+        assert_eq!(format(KeywordCase::Upper), "SELECT\n  a,\n  B\nFROM\n  t\n");
+        assert_eq!(format(KeywordCase::Lower), "select\n  a,\n  B\nfrom\n  t\n");
+        assert_eq!(
+            format(KeywordCase::Preserve),
+            "Select\n  a,\n  B\nfrom\n  t\n"
+        );
+    }
+
+    #[test]
+    fn test_layout() {
+        let sql = Sql {
+            indent: Indent::Tab,
+            lines_between_queries: 2,
+            max_inline_arguments: Some(40),
+            max_inline_top_level: Some(40),
+            ..Sql::default()
+        };
+        let input = "select a, b from t; select 1";
+        let want = "SELECT a, b\nFROM t;\n\nSELECT 1\n";
+        assert_eq!(sql.format(input).unwrap(), want);
+    }
+
+    #[test]
+    fn test_postgresql() {
+        let sql = Sql {
+            dialect: Dialect::Postgresql,
+            ..Sql::default()
+        };
+        let input = "select a::int, $1 from t where b = '$$';\n\
+                     create function f() returns int as $body$\n  select 1;\n$body$ language plpgsql";
+        let want = "SELECT\n  a::int,\n  $1\nFROM\n  t\nWHERE\n  b = '$$';\n\
+                    CREATE FUNCTION f() RETURNS int AS $body$\n  select 1;\n$body$ language plpgsql\n";
+        assert_eq!(sql.format(input).unwrap(), want);
+    }
+
+    #[test]
+    fn test_config() {
+        let sql: Sql = toml_edit::de::from_str(
+            "dialect = \"postgres\"\nkeyword-case = \"preserve\"\nindent = 4",
+        )
+        .unwrap();
+        assert_eq!(sql.dialect, Dialect::Postgresql);
+        assert_eq!(sql.keyword_case, KeywordCase::Preserve);
+        assert_eq!(sql.indent, Indent::Spaces(4));
+    }
+
+    #[test]
+    fn test_validate() {
+        let sql = Sql::default();
+        let errors = |input| sql.format(input).unwrap_err();
+        assert_eq!(
+            errors("select 'abc from t where (x = 1\nselect 2;"),
+            "unterminated string at line 1 column 8"
+        );
+        assert_eq!(
+            errors("select 'a;\nselect 1"),
+            "unterminated string at line 1 column 8"
+        );
+        assert_eq!(
+            errors("select (a from t);\nselect a) from t;\n/* x"),
+            "unmatched ')' at line 2 column 9\nunterminated comment at line 3 column 1"
+        );
+        assert_eq!(
+            errors("select count(* from t"),
+            "unclosed '(' at line 1 column 13"
+        );
+        assert_eq!(
+            errors("select $$ a"),
+            "unterminated dollar-quoted string at line 1 column 8"
+        );
+        assert_eq!(
+            errors("select 1;\n\nlorem ipsum;"),
+            "unrecognized statement \"lorem\" at line 3 column 1"
+        );
+        assert_eq!(
+            errors("select 1;\n, 2;"),
+            "unexpected \",\" at the start of a statement at line 2 column 1"
+        );
+
+        let valid = [
+            "\\echo x\nretry: select 'it''s', \"a\"\"b\", `c` -- d)\n;",
+            "unlock tables;\nanalyse t;\nabort;\ninstall httpfs;",
+            "put file:///tmp/data.csv @stage;",
+            "select (data #> '{a}') from t;",
+        ];
+        for input in valid {
+            assert!(sql.format(input).is_ok(), "{input}");
+        }
+        let mysql = Sql {
+            dialect: Dialect::Mysql,
+            ..sql.clone()
+        };
+        assert_eq!(mysql.format("select 1 # (\n").unwrap(), "SELECT\n  1 # (\n");
+        assert_eq!(errors("select 1 # (\n"), "unclosed '(' at line 1 column 12");
+
+        let sql = Sql {
+            validate: Validate::Warn,
+            ..Sql::default()
+        };
+        assert!(sql.format("select (").is_ok());
+        assert_eq!(
+            sql.warnings(None, "select ("),
+            ["unclosed '(' at line 1 column 8"]
+        );
+    }
+
+    #[test]
+    fn test_templating() {
+        let sql = Sql {
+            templating: Templating::Jinja,
+            keyword_case: KeywordCase::Lower,
+            ..Sql::default()
+        };
+        let input = "{{ config(materialized='table') }}\n\
+                     select id,\n\
+                     {% for col in cols %}\n\
+                     sum({{ col }}) as {{ col }}_total,\n\
+                     {% endfor %}\n\
+                     count(*) as n\n\
+                     from {{ ref('orders') }}\n\
+                     {# incremental #}\n\
+                     {% if is_incremental() %}where id > 1{% endif %}";
+        let want = "{{ config(materialized='table') }}\n\
+                    select\n  id,\n\
+                    \x20 {% for col in cols %}\n\
+                    \x20   sum({{ col }}) as {{ col }}_total,\n\
+                    \x20 {% endfor %}\n\
+                    \x20 count(*) as n\n\
+                    from\n  {{ ref('orders') }}\n\
+                    \x20 {# incremental #}\n\
+                    \x20 {% if is_incremental() %}where id > 1{% endif %}\n";
+        let out = sql.format(input).unwrap();
+        assert_eq!(out, want);
+        assert_eq!(sql.format(&out).unwrap(), out);
+    }
+
+    #[test]
+    fn test_templating_auto() {
+        let root = std::env::temp_dir().join(format!("metafmt-dbt-{}", std::process::id()));
+        let models = root.join("models");
+        std::fs::create_dir_all(&models).unwrap();
+        std::fs::write(root.join("dbt_project.yml"), "name: x\n").unwrap();
+
+        let input = "select * from {{ ref('x') }}";
+        let sql = Sql::default();
+        let want = "SELECT\n  *\nFROM\n  {{ ref('x') }}\n";
+        assert_eq!(sql.format_file(&models.join("x.sql"), input).unwrap(), want);
+        assert!(!sql.is_templated(Some(Path::new("x.sql"))));
+        assert!(!sql.is_templated(None));
+
+        // The result is cached for the directories that were looked up.
+        assert_eq!(sql.dbt_dirs.lock().unwrap().get(&models), Some(&true));
+        std::fs::remove_dir_all(&root).unwrap();
+        assert!(sql.is_templated(Some(&models.join("y.sql"))));
+    }
+}
@@ -1,15 +1,845 @@
-use toml_edit::DocumentMut;
+use std::{collections::HashMap, path::Path};
+
+use serde::Deserialize;
+use toml_edit::{Array, Decor, DocumentMut, InlineTable, Item, KeyMut, RawString, Table, Value};
 
 use super::Format;
 
-#[derive(Clone, Copy, Default)]
-pub(crate) struct Toml {}
+/// Toml formats TOML documents.
+#[derive(Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Toml {
+    /// Dotted paths of tables whose keys are sorted alphabetically, e.g.
+    /// "dependencies" or "target.*.dependencies". A "*" matches any key.
+    /// Keys separated by blank lines are sorted as separate groups, and
+    /// comments move along with the key that they precede.
+    pub sort_tables: Vec<String>,
+    /// Applies the canonical table order and sorting rules for well-known
+    /// files, such as Cargo.toml and pyproject.toml.
+    pub presets: bool,
+}
 
 impl Format for Toml {
     fn format(&self, input: &str) -> Result<String, String> {
-        input
+        self.format_toml(input, None)
+    }
+
+    fn format_file(&self, path: &Path, input: &str) -> Result<String, String> {
+        let preset = match self.presets {
+            true => path
+                .file_name()
+                .and_then(std::ffi::OsStr::to_str)
+                .and_then(Preset::for_file_name),
+            false => None,
+        };
+        self.format_toml(input, preset)
+    }
+}
+
+impl Toml {
+    fn format_toml(&self, input: &str, preset: Option<&Preset>) -> Result<String, String> {
+        let mut doc = input
             .parse::<DocumentMut>()
-            .map(|doc| doc.to_string())
-            .map_err(|err| err.to_string())
+            .map_err(|err| err.to_string())?;
+
+        if let Some(preset) = preset {
+            order_tables(doc.as_table_mut(), preset.tables);
+        }
+        let preset_paths = preset.map(|p| p.sort_tables).unwrap_or_default();
+        let paths = self.sort_tables.iter().map(String::as_str);
+        for path in paths.chain(preset_paths.iter().copied()) {
+            let path = path.split('.').collect::<Vec<_>>();
+            sort_tables(doc.as_table_mut(), &path);
+        }
+
+        Ok(format_document(doc))
+    }
+}
+
+const INDENT_WIDTH: usize = 2;
+const MAX_LINE_LENGTH: usize = 100;
+
+fn format_document(mut doc: DocumentMut) -> String {
+    format_body(doc.as_table_mut(), &mut Vec::new(), &mut true);
+    format_tables(doc.as_table_mut());
+
+    let trailing = parse_trivia(raw_str(doc.trailing()), true);
+    doc.set_trailing(render_lines(&trailing.lines, "", true));
+
+    // Leading and trailing whitespace can only ever be trivia, so it's safe
+    // to trim the rendered document.
+    let out = doc.to_string();
+    let out = out.trim_start_matches(['\n', ' ', '\t']).trim_end();
+    if out.is_empty() {
+        return String::new();
+    }
+    format!("{out}\n")
+}
+
+// --- Presets ---
+
+/// Preset describes the canonical layout of a well-known file.
+struct Preset {
+    /// The order of top-level tables. Unknown tables are placed after these,
+    /// in their original order.
+    tables: &'static [&'static str],
+    /// Dotted paths of tables whose keys are sorted.
+    sort_tables: &'static [&'static str],
+}
+
+const CARGO: Preset = Preset {
+    tables: &[
+        "package",
+        "workspace",
+        "lib",
+        "bin",
+        "example",
+        "test",
+        "bench",
+        "features",
+        "dependencies",
+        "dev-dependencies",
+        "build-dependencies",
+        "target",
+        "badges",
+        "lints",
+        "patch",
+        "replace",
+        "profile",
+    ],
+    sort_tables: &[
+        "dependencies",
+        "dev-dependencies",
+        "build-dependencies",
+        "target.*.dependencies",
+        "target.*.dev-dependencies",
+        "target.*.build-dependencies",
+        "workspace.dependencies",
+    ],
+};
+
+const PYPROJECT: Preset = Preset {
+    tables: &["build-system", "project", "dependency-groups", "tool"],
+    sort_tables: &[
+        "project.optional-dependencies",
+        "dependency-groups",
+        "tool.uv.sources",
+    ],
+};
+
+impl Preset {
+    fn for_file_name(name: &str) -> Option<&'static Self> {
+        match name {
+            "Cargo.toml" => Some(&CARGO),
+            "pyproject.toml" => Some(&PYPROJECT),
+            _ => None,
+        }
+    }
+}
+
+// --- Sorting ---
+
+/// Reorders the top-level tables of a document to match the provided order,
+/// keeping each table's sub-tables together.
+fn order_tables(root: &mut Table, order: &[&str]) {
+    let mut keys = Vec::new();
+    for (key, item) in root.iter() {
+        let mut positions = Vec::new();
+        header_positions(item, &mut positions);
+        if let Some(first) = positions.iter().min() {
+            let rank = order.iter().position(|v| *v == key).unwrap_or(order.len());
+            keys.push((rank, *first, key.to_string(), positions));
+        }
+    }
+    keys.sort_by_key(|(rank, first, _, _)| (*rank, *first));
+
+    let mut next = 1;
+    let mut new_positions = HashMap::new();
+    for (_, _, _, mut positions) in keys {
+        positions.sort_unstable();
+        for position in positions {
+            new_positions.insert(position, next);
+            next += 1;
+        }
+    }
+    for (_, item) in root.iter_mut() {
+        set_header_positions(item, &new_positions);
+    }
+}
+
+fn header_positions(item: &Item, out: &mut Vec<isize>) {
+    let tables: Box<dyn Iterator<Item = &Table>> = match item {
+        Item::Table(t) => Box::new(std::iter::once(t)),
+        Item::ArrayOfTables(tables) => Box::new(tables.iter()),
+        _ => return,
+    };
+    for t in tables {
+        if let Some(position) = t.position().filter(|_| !t.is_implicit() && !t.is_dotted()) {
+            out.push(position);
+        }
+        for (_, item) in t.iter() {
+            header_positions(item, out);
+        }
+    }
+}
+
+fn set_header_positions(item: &mut Item, positions: &HashMap<isize, isize>) {
+    let tables: Box<dyn Iterator<Item = &mut Table>> = match item {
+        Item::Table(t) => Box::new(std::iter::once(t)),
+        Item::ArrayOfTables(tables) => Box::new(tables.iter_mut()),
+        _ => return,
+    };
+    for t in tables {
+        if let Some(position) = t.position().and_then(|v| positions.get(&v)) {
+            t.set_position(Some(*position));
+        }
+        for (_, item) in t.iter_mut() {
+            set_header_positions(item, positions);
+        }
+    }
+}
+
+/// Sorts the keys of every table matching the provided path.
+fn sort_tables(table: &mut Table, path: &[&str]) {
+    let Some((first, rest)) = path.split_first() else {
+        sort_table(table);
+        return;
+    };
+    for (key, item) in table.iter_mut() {
+        if *first != "*" && key.get() != *first {
+            continue;
+        }
+        match item {
+            Item::Table(t) => sort_tables(t, rest),
+            Item::ArrayOfTables(tables) => {
+                for t in tables.iter_mut() {
+                    sort_tables(t, rest);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Sorts the keys of a table alphabetically. Runs of keys separated by a
+/// blank line are sorted independently, and the blank lines and comments
+/// that start a run stay at the start of the run.
+fn sort_table(table: &mut Table) {
+    // Tables with headers are rendered by position, so only the key/value
+    // pairs (including dotted keys) are grouped.
+    let keys = table
+        .iter()
+        .filter(|(_, item)| match item {
+            Item::Value(_) => true,
+            Item::Table(t) => t.is_dotted(),
+            _ => false,
+        })
+        .map(|(k, _)| k.to_string())
+        .collect::<Vec<_>>();
+    let mut groups = HashMap::new();
+    let mut headers = Vec::new();
+    for key in &keys {
+        let header = with_leaf_decor(table, key, |decor| {
+            let prefix = decor.prefix().map(raw_str).unwrap_or_default();
+            if !has_blank_line(prefix) {
+                return None;
+            }
+            let header = prefix.to_string();
+            decor.set_prefix("");
+            Some(header)
+        })
+        .flatten();
+        if header.is_some() || headers.is_empty() {
+            headers.push(header.unwrap_or_default());
+        }
+        groups.insert(key.clone(), headers.len() - 1);
+    }
+
+    table.sort_values_by(|k1, _, k2, _| {
+        let g1 = groups.get(k1.get()).copied().unwrap_or(usize::MAX);
+        let g2 = groups.get(k2.get()).copied().unwrap_or(usize::MAX);
+        (g1, k1.get()).cmp(&(g2, k2.get()))
+    });
+
+    let mut group = None;
+    let keys = table.iter().map(|(k, _)| k.to_string()).collect::<Vec<_>>();
+    for key in keys {
+        let Some(current) = groups.get(&key).copied() else {
+            continue;
+        };
+        if group == Some(current) {
+            continue;
+        }
+        group = Some(current);
+        let header = &headers[current];
+        with_leaf_decor(table, &key, |decor| {
+            let prefix = decor.prefix().map(raw_str).unwrap_or_default();
+            decor.set_prefix(format!("{header}{prefix}"));
+        });
+    }
+}
+
+/// Calls the provided function with the decor of the first key in a line,
+/// following dotted keys such as "a.b.c = 1" down to their leaf.
+fn with_leaf_decor<R>(table: &mut Table, key: &str, f: impl FnOnce(&mut Decor) -> R) -> Option<R> {
+    let inner = match table.get(key)? {
+        Item::Table(t) if t.is_dotted() => Some(t.iter().next()?.0.to_string()),
+        _ => None,
+    };
+    match inner {
+        Some(inner) => with_leaf_decor(table.get_mut(key)?.as_table_mut()?, &inner, f),
+        None => Some(f(table.key_mut(key)?.leaf_decor_mut())),
+    }
+}
+
+/// Returns true if the prefix of a key contains a blank line.
+fn has_blank_line(prefix: &str) -> bool {
+    let mut lines = prefix.split('\n');
+    lines.next_back();
+    lines.any(|line| line.trim().is_empty())
+}
+
+// --- Tables ---
+
+/// Formats every table with a header nested under the provided table.
+fn format_tables(table: &mut Table) {
+    for (mut key, item) in table.iter_mut() {
+        match item {
+            Item::Table(t) if t.is_dotted() => format_tables(t),
+            Item::Table(t) => {
+                clear_key_decor(&mut key);
+                format_header(t.decor_mut());
+                format_body(t, &mut Vec::new(), &mut true);
+                format_tables(t);
+            }
+            Item::ArrayOfTables(tables) => {
+                clear_key_decor(&mut key);
+                for t in tables.iter_mut() {
+                    format_header(t.decor_mut());
+                    format_body(t, &mut Vec::new(), &mut true);
+                    format_tables(t);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Places exactly one blank line before a table header, followed by any
+/// comments, and normalizes the comment on the header's line.
+fn format_header(decor: &mut Decor) {
+    let prefix = parse_trivia(decor.prefix().map(raw_str).unwrap_or_default(), true);
+    let lines = prefix.lines.iter().skip_while(|line| line.is_none());
+    let mut out = String::from("\n");
+    out.push_str(&render_lines(
+        &lines.cloned().collect::<Vec<_>>(),
+        "",
+        false,
+    ));
+    decor.set_prefix(out);
+
+    let suffix = parse_trivia(decor.suffix().map(raw_str).unwrap_or_default(), false);
+    decor.set_suffix(render_trailing(suffix.trailing));
+}
+
+/// Formats the key/value pairs of a table, including those nested under
+/// dotted keys.
+fn format_body(table: &mut Table, path: &mut Vec<String>, first: &mut bool) {
+    for (mut key, item) in table.iter_mut() {
+        match item {
+            Item::Value(value) => {
+                let prefix = key.leaf_decor().prefix().map(raw_str).unwrap_or_default();
+                let trivia = parse_trivia(prefix, true);
+                let prefix = render_lines(&trivia.lines, "", !*first);
+                key.leaf_decor_mut().set_prefix(prefix);
+                key.leaf_decor_mut().set_suffix(" ");
+                key.dotted_decor_mut().clear();
+                *first = false;
+
+                // The value starts after "key = ".
+                let key_len: usize = path.iter().map(|k| k.chars().count() + 1).sum();
+                let column = key_len + key.to_string().chars().count() + 3;
+                format_value(value, 0, column);
+            }
+            Item::Table(t) if t.is_dotted() => {
+                clear_key_decor(&mut key);
+                path.push(key.to_string());
+                format_body(t, path, first);
+                path.pop();
+            }
+            _ => {}
+        }
+    }
+}
+
+fn clear_key_decor(key: &mut KeyMut<'_>) {
+    key.leaf_decor_mut().clear();
+    key.dotted_decor_mut().clear();
+}
+
+// --- Values ---
+
+/// Formats a value that follows "key = " in a table, starting at the
+/// provided column.
+fn format_value(value: &mut Value, indent: usize, column: usize) {
+    let suffix = parse_trivia(
+        value.decor().suffix().map(raw_str).unwrap_or_default(),
+        false,
+    );
+    let suffix = render_trailing(suffix.trailing);
+    value.decor_mut().clear();
+    match value {
+        Value::Array(array) => format_array(array, indent, column),
+        Value::InlineTable(table) => format_inline_table(table),
+        _ => {}
+    }
+    value.decor_mut().set_prefix(" ");
+    value.decor_mut().set_suffix(suffix);
+}
+
+/// Formats an array on a single line if it fits within the maximum line
+/// length and has no comments, otherwise places each element on its own line
+/// with a trailing comma.
+fn format_array(array: &mut Array, indent: usize, column: usize) {
+    // Comments can only be moved if they are between a comma and the next
+    // element, so leave anything more unusual as it is.
+    if array
+        .iter()
+        .any(|value| value.decor().suffix().is_some_and(has_comment))
+    {
+        return;
+    }
+    if !array_has_comments(array) {
+        collapse_array(array);
+        if column + array.to_string().chars().count() <= MAX_LINE_LENGTH {
+            return;
+        }
+    }
+
+    let inner = " ".repeat(indent + INDENT_WIDTH);
+    let len = array.len();
+    for (i, value) in array.iter_mut().enumerate() {
+        let prefix = parse_trivia(
+            value.decor().prefix().map(raw_str).unwrap_or_default(),
+            false,
+        );
+        let mut out = render_trailing(prefix.trailing);
+        out.push('\n');
+        out.push_str(&render_lines(&prefix.lines, &inner, i > 0));
+        out.push_str(&inner);
+
+        value.decor_mut().clear();
+        match value {
+            // Account for the trailing comma.
+            Value::Array(array) => format_array(array, indent + INDENT_WIDTH, inner.len() + 1),
+            Value::InlineTable(table) => format_inline_table(table),
+            _ => {}
+        }
+        value.decor_mut().set_prefix(out);
+        value.decor_mut().set_suffix("");
+    }
+
+    let trailing = parse_trivia(raw_str(array.trailing()), false);
+    let mut out = render_trailing(trailing.trailing);
+    let mut lines = trailing.lines;
+    while lines.last().is_some_and(Option::is_none) {
+        lines.pop();
+    }
+    out.push('\n');
+    out.push_str(&render_lines(&lines, &inner, len > 0));
+    out.push_str(&" ".repeat(indent));
+    array.set_trailing(out);
+    array.set_trailing_comma(len > 0);
+}
+
+/// Removes all whitespace between the elements of an array, which must not
+/// contain any comments.
+fn collapse_array(array: &mut Array) {
+    for value in array.iter_mut() {
+        value.decor_mut().clear();
+        match value {
+            Value::Array(array) => collapse_array(array),
+            Value::InlineTable(table) => format_inline_table(table),
+            _ => {}
+        }
+    }
+    array.set_trailing("");
+    array.set_trailing_comma(false);
+}
+
+fn array_has_comments(array: &Array) -> bool {
+    has_comment(array.trailing())
+        || array.iter().any(|value| {
+            decor_has_comment(value.decor())
+                || match value {
+                    Value::Array(array) => array_has_comments(array),
+                    Value::InlineTable(table) => inline_table_has_comments(table),
+                    _ => false,
+                }
+        })
+}
+
+/// Normalizes the whitespace in an inline table to the form "{ a = 1, b = 2 }".
+/// Inline tables containing comments are left as they are.
+fn format_inline_table(table: &mut InlineTable) {
+    if inline_table_has_comments(table) {
+        return;
+    }
+    for (mut key, value) in table.iter_mut() {
+        clear_key_decor(&mut key);
+        value.decor_mut().clear();
+        match value {
+            Value::Array(array) => collapse_array(array),
+            Value::InlineTable(table) => format_inline_table(table),
+            _ => {}
+        }
+    }
+    table.set_trailing("");
+    table.set_trailing_comma(false);
+}
+
+fn inline_table_has_comments(table: &InlineTable) -> bool {
+    has_comment(table.trailing())
+        || table.iter().any(|(key, value)| {
+            table.key(key).is_some_and(|key| {
+                decor_has_comment(key.leaf_decor()) || decor_has_comment(key.dotted_decor())
+            }) || decor_has_comment(value.decor())
+                || match value {
+                    Value::Array(array) => array_has_comments(array),
+                    Value::InlineTable(table) => inline_table_has_comments(table),
+                    _ => false,
+                }
+        })
+}
+
+// --- Trivia ---
+
+fn raw_str(raw: &RawString) -> &str {
+    raw.as_str().unwrap_or_default()
+}
+
+fn has_comment(raw: &RawString) -> bool {
+    raw_str(raw).contains('#')
+}
+
+fn decor_has_comment(decor: &Decor) -> bool {
+    decor.prefix().is_some_and(has_comment) || decor.suffix().is_some_and(has_comment)
+}
+
+/// Trivia holds the comments found in the whitespace between items.
+struct Trivia<'a> {
+    /// A comment on the same line as the preceding item.
+    trailing: Option<&'a str>,
+    /// The lines that follow, where None represents a blank line.
+    lines: Vec<Option<&'a str>>,
+}
+
+/// Parses whitespace and comments. If `starts_line` is false, the text before
+/// the first newline belongs to the line of the preceding item.
+fn parse_trivia(raw: &str, starts_line: bool) -> Trivia<'_> {
+    let mut segments: Vec<&str> = raw.split('\n').collect();
+    // The last segment is the indentation before the next item.
+    let last = segments.pop().unwrap_or_default();
+    let mut trailing = None;
+    if !starts_line {
+        if segments.is_empty() {
+            // No newline, so everything is on the preceding item's line.
+            return Trivia {
+                trailing: comment(last),
+                lines: Vec::new(),
+            };
+        }
+        trailing = comment(segments.remove(0));
+    }
+    Trivia {
+        trailing,
+        lines: segments.into_iter().map(comment).collect(),
+    }
+}
+
+fn comment(s: &str) -> Option<&str> {
+    let s = s.trim();
+    if s.is_empty() {
+        None
+    } else {
+        Some(s)
+    }
+}
+
+/// Renders lines of comments at the provided indentation, collapsing
+/// consecutive blank lines. Leading blank lines are only kept if
+/// `leading_blank` is true.
+fn render_lines(lines: &[Option<&str>], indent: &str, leading_blank: bool) -> String {
+    let mut out = String::new();
+    let mut prev_blank = !leading_blank;
+    for line in lines {
+        match line {
+            None if prev_blank => {}
+            None => {
+                out.push('\n');
+                prev_blank = true;
+            }
+            Some(comment) => {
+                out.push_str(indent);
+                out.push_str(comment);
+                out.push('\n');
+                prev_blank = false;
+            }
+        }
+    }
+    out
+}
+
+fn render_trailing(comment: Option<&str>) -> String {
+    comment.map(|c| format!(" {c}")).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format_toml(input: &str) -> Result<String, String> {
+        Toml::default().format(input)
+    }
+
+    #[test]
+    fn test_empty_input() {
+        assert_eq!(format_toml("").unwrap(), "");
+        assert_eq!(format_toml("\n\n").unwrap(), "");
+    }
+
+    #[test]
+    fn test_key_value_spacing() {
+        let input = "a=1\nb   =    \"x\"   # comment\n  c.d = true\n";
+        assert_eq!(
+            format_toml(input).unwrap(),
+            "a = 1\nb = \"x\" # comment\nc.d = true\n"
+        );
+    }
+
+    #[test]
+    fn test_table_indentation() {
+        let input = "[ package ]\n    name = \"x\"\n      version = \"1\"\n";
+        assert_eq!(
+            format_toml(input).unwrap(),
+            "[package]\nname = \"x\"\nversion = \"1\"\n"
+        );
+    }
+
+    #[test]
+    fn test_blank_lines_between_tables() {
+        let input = "\n\na = 1\n[b]\nc = 2\n\n\n\n[[d]]\ne = 3\n\n\n";
+        assert_eq!(
+            format_toml(input).unwrap(),
+            "a = 1\n\n[b]\nc = 2\n\n[[d]]\ne = 3\n"
+        );
+    }
+
+    #[test]
+    fn test_blank_lines_between_keys() {
+        let input = "[a]\n\nb = 1\n\n\n\nc = 2\n";
+        assert_eq!(format_toml(input).unwrap(), "[a]\nb = 1\n\nc = 2\n");
+    }
+
+    #[test]
+    fn test_comments() {
+        let input = "# top\n\n  # about a\na = 1\n\n   # about b\n[b] # header\n  # inside\nc = 2\n\n# end\n";
+        assert_eq!(
+            format_toml(input).unwrap(),
+            "# top\n\n# about a\na = 1\n\n# about b\n[b] # header\n# inside\nc = 2\n\n# end\n"
+        );
+    }
+
+    #[test]
+    fn test_short_array_collapsed() {
+        let input = "a = [\n    1,\n    2,\n    3,\n]\nb = [ 1,2 ,3 ]\n";
+        assert_eq!(
+            format_toml(input).unwrap(),
+            "a = [1, 2, 3]\nb = [1, 2, 3]\n"
+        );
+    }
+
+    #[test]
+    fn test_long_array_wrapped() {
+        let items: Vec<String> = (0..12).map(|i| format!("\"item-{i}\"")).collect();
+        let input = format!("key = [{}]\n", items.join(", "));
+        let expected = format!(
+            "key = [\n{}]\n",
+            items
+                .iter()
+                .map(|i| format!("  {i},\n"))
+                .collect::<String>()
+        );
+        assert_eq!(format_toml(&input).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_array_comments() {
+        let input = "a = [ # open\n    1, # one\n  # before two\n      2\n] # after\n";
+        assert_eq!(
+            format_toml(input).unwrap(),
+            "a = [ # open\n  1, # one\n  # before two\n  2,\n] # after\n"
+        );
+    }
+
+    #[test]
+    fn test_inline_table() {
+        let input = "a = {x=1,   y = [ 1,2 ],z={}}\nb = {  }\n";
+        assert_eq!(
+            format_toml(input).unwrap(),
+            "a = { x = 1, y = [1, 2], z = {} }\nb = {}\n"
+        );
+    }
+
+    #[test]
+    fn test_multiline_string_preserved() {
+        let input = "a = \"\"\"\nline one\n\n\n   line two\n\"\"\"\n";
+        assert_eq!(format_toml(input).unwrap(), input);
+    }
+
+    #[test]
+    fn test_idempotent() {
+        let input = "# c\n[a]\nb = [ # x\n 1,\n]\n[c.d]\ne = { f = 1 }\n";
+        let once = format_toml(input).unwrap();
+        assert_eq!(format_toml(&once).unwrap(), once);
+    }
+
+    #[test]
+    fn test_invalid_toml() {
+        assert!(format_toml("a = \n").is_err());
+    }
+
+    #[test]
+    fn test_sort_tables() {
+        let toml = Toml {
+            sort_tables: vec![
+                "dependencies".to_string(),
+                "target.*.dependencies".to_string(),
+            ],
+            ..Toml::default()
+        };
+        let input = r#"[dependencies]
+serde = "1" # serialization
+# Parsing.
+anyhow = "1"
+clap.version = "4"
+
+# Async runtime.
+tokio = "1"
+futures = "0.3"
+
+[target.'cfg(unix)'.dependencies]
+nix = "0.29"
+libc = "0.2"
+"#;
+        let want = r#"[dependencies]
+# Parsing.
+anyhow = "1"
+clap.version = "4"
+serde = "1" # serialization
+
+# Async runtime.
+futures = "0.3"
+tokio = "1"
+
+[target.'cfg(unix)'.dependencies]
+libc = "0.2"
+nix = "0.29"
+"#;
+        assert_eq!(toml.format(input).unwrap(), want);
+    }
+
+    #[test]
+    fn test_sort_tables_with_sub_tables() {
+        let toml = Toml {
+            sort_tables: vec!["dependencies".to_string()],
+            ..Toml::default()
+        };
+        let input = "[dependencies]\nb = \"1\"\n\n# Group.\nd = \"1\"\nc = \"1\"\n\n[dependencies.a]\nversion = \"1\"\n";
+        let want = "[dependencies]\nb = \"1\"\n\n# Group.\nc = \"1\"\nd = \"1\"\n\n[dependencies.a]\nversion = \"1\"\n";
+        assert_eq!(toml.format(input).unwrap(), want);
+    }
+
+    #[test]
+    fn test_cargo_preset() {
+        let input = r#"[dependencies]
+b = "1"
+a = "1"
+
+[profile.release]
+lto = true
+
+[package]
+name = "x"
+
+[[bin]]
+name = "x"
+
+[features]
+default = []
+"#;
+        let want = r#"[package]
+name = "x"
+
+[[bin]]
+name = "x"
+
+[features]
+default = []
+
+[dependencies]
+a = "1"
+b = "1"
+
+[profile.release]
+lto = true
+"#;
+        let toml = Toml {
+            presets: true,
+            ..Toml::default()
+        };
+        let path = Path::new("crate/Cargo.toml");
+        assert_eq!(toml.format_file(path, input).unwrap(), want);
+        assert_eq!(
+            toml.format_file(Path::new("other.toml"), input).unwrap(),
+            input
+        );
+        assert_eq!(Toml::default().format_file(path, input).unwrap(), input);
+    }
+
+    #[test]
+    fn test_pyproject_preset() {
+        let input = r#"[tool.ruff]
+line-length = 100
+
+[project]
+name = "x"
+
+[project.optional-dependencies]
+test = ["pytest"]
+dev = ["ruff"]
+
+[build-system]
+requires = ["hatchling"]
+"#;
+        let want = r#"[build-system]
+requires = ["hatchling"]
+
+[project]
+name = "x"
+
+[project.optional-dependencies]
+dev = ["ruff"]
+test = ["pytest"]
+
+[tool.ruff]
+line-length = 100
+"#;
+        let toml = Toml {
+            presets: true,
+            ..Toml::default()
+        };
+        let path = Path::new("pyproject.toml");
+        assert_eq!(toml.format_file(path, input).unwrap(), want);
     }
 }
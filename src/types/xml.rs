@@ -0,0 +1,546 @@
+use std::ops::Range;
+
+use serde::Deserialize;
+use xmlparser::{ElementEnd, StrSpan, Token, Tokenizer};
+
+use super::{Format, Indent, Locator};
+
+/// Xml formats XML documents, such as SVG images and MSBuild projects.
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Xml {
+    /// The indentation of nested elements.
+    pub indent: Indent,
+    /// The line width past which the attributes of a start tag are written
+    /// on separate lines.
+    pub line_width: usize,
+    /// How elements without any content are written.
+    pub empty_elements: EmptyElements,
+}
+
+impl Default for Xml {
+    fn default() -> Self {
+        Xml {
+            indent: Indent::default(),
+            line_width: 100,
+            empty_elements: EmptyElements::default(),
+        }
+    }
+}
+
+/// EmptyElements controls how elements without any content are written.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EmptyElements {
+    /// Write them as a self-closing tag, e.g. `<a />`.
+    #[default]
+    Collapse,
+    /// Write them with a start and end tag, e.g. `<a></a>`.
+    Expand,
+    /// Keep them as written.
+    Preserve,
+}
+
+impl Format for Xml {
+    fn format(&self, input: &str) -> Result<String, String> {
+        let input = input.replace("\r\n", "\n");
+        let (bom, input) = match input.strip_prefix('\u{feff}') {
+            Some(rest) => ("\u{feff}", rest),
+            None => ("", input.as_str()),
+        };
+        if input.trim().is_empty() {
+            return Ok(String::new());
+        }
+
+        let nodes = parse(input)?;
+        let mut writer = Writer {
+            xml: self,
+            input,
+            indent: self.indent.to_str(),
+            out: bom.to_string(),
+        };
+        writer.write_nodes(&nodes, 0);
+        Ok(writer.out)
+    }
+}
+
+/// Node is a part of a document, keeping the source text of everything that
+/// isn't reformatted.
+enum Node<'a> {
+    Declaration(String),
+    Element(Element<'a>),
+    Text(&'a str),
+    Cdata(&'a str),
+    Comment(StrSpan<'a>),
+    /// A processing instruction or document type declaration.
+    Markup(&'a str),
+}
+
+struct Element<'a> {
+    name: &'a str,
+    attributes: Vec<Attribute<'a>>,
+    children: Vec<Node<'a>>,
+    /// The range of the source between the start and end tags, or None for
+    /// a self-closing tag.
+    content: Option<Range<usize>>,
+}
+
+struct Attribute<'a> {
+    name: &'a str,
+    value: &'a str,
+    /// Whether the value was written in single quotes.
+    single_quoted: bool,
+}
+
+impl Element<'_> {
+    /// Returns true if the element has `xml:space="preserve"`, in which case
+    /// all of its content is significant.
+    fn preserves_space(&self) -> bool {
+        self.attributes
+            .iter()
+            .any(|attr| attr.name == "xml:space" && attr.value == "preserve")
+    }
+
+    /// Returns true if the element has no content other than whitespace.
+    fn is_empty(&self) -> bool {
+        self.children
+            .iter()
+            .all(|node| matches!(node, Node::Text(text) if text.trim().is_empty()))
+    }
+
+    /// Returns true if the element's content is written as it is, either
+    /// because it only holds text or because the text is mixed with other
+    /// nodes, where the whitespace around them is significant.
+    fn is_inline(&self) -> bool {
+        self.children
+            .iter()
+            .all(|node| matches!(node, Node::Text(_) | Node::Cdata(_)))
+            || self
+                .children
+                .iter()
+                .any(|node| matches!(node, Node::Text(text) if !text.trim().is_empty()))
+    }
+}
+
+impl Attribute<'_> {
+    /// Writes the attribute with its value in double quotes.
+    fn write(&self, out: &mut String) {
+        out.push_str(self.name);
+        out.push_str("=\"");
+        match self.single_quoted {
+            true => out.push_str(&self.value.replace('"', "&quot;")),
+            false => out.push_str(self.value),
+        }
+        out.push('"');
+    }
+}
+
+/// Parses the input into its top-level nodes, checking that every element is
+/// closed by a matching end tag.
+fn parse(input: &str) -> Result<Vec<Node<'_>>, String> {
+    let mut nodes = Vec::new();
+    let mut open: Vec<(Element, usize)> = Vec::new();
+    let mut doctype_start = None;
+
+    for token in Tokenizer::from(input) {
+        let node = match token.map_err(|err| parse_error(&err))? {
+            Token::Declaration {
+                version,
+                encoding,
+                standalone,
+                ..
+            } => {
+                let mut decl = format!("<?xml version=\"{version}\"");
+                if let Some(encoding) = encoding {
+                    decl.push_str(&format!(" encoding=\"{encoding}\""));
+                }
+                if let Some(standalone) = standalone {
+                    let value = if standalone { "yes" } else { "no" };
+                    decl.push_str(&format!(" standalone=\"{value}\""));
+                }
+                decl.push_str("?>");
+                Node::Declaration(decl)
+            }
+            Token::ProcessingInstruction { span, .. } | Token::EmptyDtd { span, .. } => {
+                Node::Markup(span.as_str())
+            }
+            Token::Comment { span, .. } => Node::Comment(span),
+            Token::DtdStart { span, .. } => {
+                doctype_start = Some(span.start());
+                continue;
+            }
+            Token::EntityDeclaration { .. } => continue,
+            Token::DtdEnd { span } => {
+                let start = doctype_start.take().unwrap_or(span.start());
+                Node::Markup(&input[start..span.end()])
+            }
+            Token::ElementStart {
+                prefix,
+                local,
+                span,
+            } => {
+                let element = Element {
+                    name: qualified_name(input, prefix, local),
+                    attributes: Vec::new(),
+                    children: Vec::new(),
+                    content: None,
+                };
+                open.push((element, span.start()));
+                continue;
+            }
+            Token::Attribute {
+                prefix,
+                local,
+                value,
+                ..
+            } => {
+                if let Some((element, _)) = open.last_mut() {
+                    element.attributes.push(Attribute {
+                        name: qualified_name(input, prefix, local),
+                        value: value.as_str(),
+                        single_quoted: input[..value.start()].ends_with('\''),
+                    });
+                }
+                continue;
+            }
+            Token::ElementEnd { end, span } => match end {
+                ElementEnd::Open => {
+                    if let Some((element, _)) = open.last_mut() {
+                        element.content = Some(span.end()..span.end());
+                    }
+                    continue;
+                }
+                ElementEnd::Empty => match open.pop() {
+                    Some((element, _)) => Node::Element(element),
+                    None => continue,
+                },
+                ElementEnd::Close(prefix, local) => {
+                    let name = qualified_name(input, prefix, local);
+                    let Some((mut element, _)) = open.pop() else {
+                        let (line, column) = Locator::new(input, 1).locate(span.start());
+                        return Err(format!(
+                            "unexpected closing tag </{name}> at line {line} column {column}"
+                        ));
+                    };
+                    if element.name != name {
+                        let (line, column) = Locator::new(input, 1).locate(span.start());
+                        return Err(format!(
+                            "mismatched closing tag </{name}> at line {line} column {column}, \
+                             expected </{}>",
+                            element.name
+                        ));
+                    }
+                    if let Some(content) = &mut element.content {
+                        content.end = span.start();
+                    }
+                    Node::Element(element)
+                }
+            },
+            Token::Text { text } => {
+                if open.is_empty() && !text.trim().is_empty() {
+                    let (line, column) = Locator::new(input, 1).locate(text.start());
+                    return Err(format!(
+                        "text outside of the root element at line {line} column {column}"
+                    ));
+                }
+                Node::Text(text.as_str())
+            }
+            Token::Cdata { span, .. } => Node::Cdata(span.as_str()),
+        };
+        match open.last_mut() {
+            Some((parent, _)) => parent.children.push(node),
+            None => nodes.push(node),
+        }
+    }
+
+    if let Some((element, start)) = open.last() {
+        let (line, column) = Locator::new(input, 1).locate(*start);
+        return Err(format!(
+            "unclosed element <{}> at line {line} column {column}",
+            element.name
+        ));
+    }
+    Ok(nodes)
+}
+
+/// Returns the name as written, including its namespace prefix.
+fn qualified_name<'a>(input: &'a str, prefix: StrSpan<'a>, local: StrSpan<'a>) -> &'a str {
+    match prefix.is_empty() {
+        true => local.as_str(),
+        false => &input[prefix.start()..local.end()],
+    }
+}
+
+/// Returns the message of a parse error, with its positions written as a
+/// line and column, e.g. "invalid attribute at line 1 column 3: expected
+/// quote mark not 'c' at line 1 column 6".
+fn parse_error(err: &xmlparser::Error) -> String {
+    let message = err.to_string().replacen(" cause ", ": ", 1);
+    let mut out = String::with_capacity(message.len());
+    let mut rest = message.as_str();
+    while let Some(i) = rest.find("at ") {
+        let (before, after) = rest.split_at(i + "at ".len());
+        out.push_str(before);
+        let position = after
+            .split_once(|c: char| !c.is_ascii_digit() && c != ':')
+            .map_or(after, |(position, _)| position);
+        match position.split_once(':') {
+            Some((line, column)) if !line.is_empty() && !column.is_empty() => {
+                out.push_str(&format!("line {line} column {column}"));
+                rest = &after[position.len()..];
+            }
+            _ => rest = after,
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+struct Writer<'a> {
+    xml: &'a Xml,
+    input: &'a str,
+    indent: String,
+    out: String,
+}
+
+impl Writer<'_> {
+    /// Writes nodes on their own lines, dropping the whitespace between them
+    /// except for single blank lines. A comment that follows another node on
+    /// the same line is kept there.
+    fn write_nodes(&mut self, nodes: &[Node], depth: usize) {
+        let mut first = true;
+        let mut newlines = 0;
+        for node in nodes {
+            if let Node::Text(text) = node {
+                newlines += text.matches('\n').count();
+                continue;
+            }
+            match node {
+                Node::Comment(comment) if !first && !self.follows_newline(comment.start()) => {
+                    self.out.pop();
+                    self.out.push(' ');
+                    self.out.push_str(comment.as_str());
+                    self.out.push('\n');
+                }
+                _ => {
+                    if !first && newlines > 1 {
+                        self.out.push('\n');
+                    }
+                    self.write_node(node, depth);
+                }
+            }
+            first = false;
+            newlines = 0;
+        }
+    }
+
+    /// Returns true if there is a line break between the node that starts at
+    /// the offset and the one before it. The whitespace outside of the root
+    /// element isn't a node, so this checks the input itself.
+    fn follows_newline(&self, start: usize) -> bool {
+        let before = &self.input[..start];
+        before[before.trim_end().len()..].contains('\n')
+    }
+
+    fn write_node(&mut self, node: &Node, depth: usize) {
+        match node {
+            Node::Element(element) => self.write_element(element, depth),
+            Node::Declaration(decl) => self.write_line(decl, depth),
+            Node::Text(text) => self.write_line(text.trim(), depth),
+            Node::Comment(comment) => self.write_line(comment.as_str(), depth),
+            Node::Cdata(raw) | Node::Markup(raw) => self.write_line(raw, depth),
+        }
+    }
+
+    fn write_line(&mut self, text: &str, depth: usize) {
+        self.write_indent(depth);
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    fn write_element(&mut self, element: &Element, depth: usize) {
+        let content = match &element.content {
+            Some(content) if element.preserves_space() || !element.is_empty() => content,
+            content => {
+                let collapse = match self.xml.empty_elements {
+                    EmptyElements::Collapse => true,
+                    EmptyElements::Expand => false,
+                    EmptyElements::Preserve => content.is_none(),
+                };
+                match collapse {
+                    true => {
+                        self.write_start_tag(element, depth, " />");
+                        self.out.push('\n');
+                    }
+                    false => {
+                        self.write_start_tag(element, depth, ">");
+                        self.write_end_tag(element);
+                    }
+                }
+                return;
+            }
+        };
+
+        self.write_start_tag(element, depth, ">");
+        if element.preserves_space() || element.is_inline() {
+            self.out.push_str(&self.input[content.clone()]);
+        } else {
+            self.out.push('\n');
+            self.write_nodes(&element.children, depth + 1);
+            self.write_indent(depth);
+        }
+        self.write_end_tag(element);
+    }
+
+    /// Writes the start tag, ending with `close`. The attributes are written
+    /// on separate lines if the tag doesn't fit within the line width.
+    fn write_start_tag(&mut self, element: &Element, depth: usize, close: &str) {
+        let start = self.out.len();
+        self.write_indent(depth);
+        self.out.push('<');
+        self.out.push_str(element.name);
+        for attr in &element.attributes {
+            self.out.push(' ');
+            attr.write(&mut self.out);
+        }
+        self.out.push_str(close);
+
+        let width = self.out[start..].chars().count();
+        if width <= self.xml.line_width || element.attributes.len() < 2 {
+            return;
+        }
+        self.out.truncate(start);
+        self.write_indent(depth);
+        self.out.push('<');
+        self.out.push_str(element.name);
+        for attr in &element.attributes {
+            self.out.push('\n');
+            self.write_indent(depth + 1);
+            attr.write(&mut self.out);
+        }
+        self.out.push_str(close);
+    }
+
+    fn write_end_tag(&mut self, element: &Element) {
+        self.out.push_str("</");
+        self.out.push_str(element.name);
+        self.out.push_str(">\n");
+    }
+
+    fn write_indent(&mut self, depth: usize) {
+        for _ in 0..depth {
+            self.out.push_str(&self.indent);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format_xml(input: &str) -> String {
+        let out = Xml::default().format(input).unwrap();
+        assert_eq!(Xml::default().format(&out).unwrap(), out, "not idempotent");
+        out
+    }
+
+    #[test]
+    fn test_indentation() {
+        let input = "<?xml version='1.0' encoding=\"utf-8\"?>\n\
+                     <project>\n<modelVersion>4.0.0</modelVersion>\n\n\n\
+                     <dependencies>\n        <dependency>\n<artifactId>x</artifactId>\
+                     </dependency>\n</dependencies>\n</project>";
+        let want = "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+                    <project>\n  <modelVersion>4.0.0</modelVersion>\n\n  \
+                    <dependencies>\n    <dependency>\n      <artifactId>x</artifactId>\n    \
+                    </dependency>\n  </dependencies>\n</project>\n";
+        assert_eq!(format_xml(input), want);
+    }
+
+    #[test]
+    fn test_comments() {
+        let input = "<?xml version=\"1.0\"?>\n<!-- before -->\n<a>\n<b/> <!-- same line -->\n\
+                     <!-- own line -->\n</a>\n<!-- after -->\n";
+        let want = "<?xml version=\"1.0\"?>\n<!-- before -->\n<a>\n  <b /> <!-- same line -->\n  \
+                    <!-- own line -->\n</a>\n<!-- after -->\n";
+        assert_eq!(format_xml(input), want);
+        assert_eq!(
+            format_xml("<a/><!-- trailing -->"),
+            "<a /> <!-- trailing -->\n"
+        );
+    }
+
+    #[test]
+    fn test_attributes() {
+        let input = "<svg   width = '10'\n  title='a \"b\"'><g/></svg  >\n";
+        let want = "<svg width=\"10\" title=\"a &quot;b&quot;\">\n  <g />\n</svg>\n";
+        assert_eq!(format_xml(input), want);
+
+        let input = "<Project><ItemGroup><PackageReference Include=\"Microsoft.Extensions\" \
+                     Version=\"8.0.0\" Condition=\"'$(Os)' == 'linux'\"></PackageReference>\
+                     </ItemGroup></Project>";
+        let want = "<Project>\n  <ItemGroup>\n    <PackageReference\n      \
+                    Include=\"Microsoft.Extensions\"\n      Version=\"8.0.0\"\n      \
+                    Condition=\"'$(Os)' == 'linux'\" />\n  </ItemGroup>\n</Project>\n";
+        let xml = Xml {
+            line_width: 80,
+            ..Xml::default()
+        };
+        assert_eq!(xml.format(input).unwrap(), want);
+        assert_eq!(xml.format(want).unwrap(), want);
+        assert_eq!(format_xml(input), want);
+    }
+
+    #[test]
+    fn test_empty_elements() {
+        let input = "<a><b></b><c/><d>\n</d></a>\n";
+        assert_eq!(format_xml(input), "<a>\n  <b />\n  <c />\n  <d />\n</a>\n");
+
+        let xml = Xml {
+            empty_elements: EmptyElements::Expand,
+            ..Xml::default()
+        };
+        let want = "<a>\n  <b></b>\n  <c></c>\n  <d></d>\n</a>\n";
+        assert_eq!(xml.format(input).unwrap(), want);
+
+        let xml = Xml {
+            empty_elements: EmptyElements::Preserve,
+            ..Xml::default()
+        };
+        let want = "<a>\n  <b></b>\n  <c />\n  <d></d>\n</a>\n";
+        assert_eq!(xml.format(input).unwrap(), want);
+    }
+
+    #[test]
+    fn test_preserved_content() {
+        let input = "<!DOCTYPE note [\n<!ENTITY x \"y\">\n]>\n<?xml-stylesheet href=\"a.xsl\"?>\n\
+                     <root>\n<!-- a\n   comment -->\n<p>Some <b>bold</b>  text</p>\
+                     <code xml:space=\"preserve\">\n  <x>  </x>\n</code>\n\
+                     <script><![CDATA[ if (a < b) {} ]]></script>\n\
+                     <v> spaced &amp; text </v> <!-- inline -->\n</root>\n";
+        let want = "<!DOCTYPE note [\n<!ENTITY x \"y\">\n]>\n<?xml-stylesheet href=\"a.xsl\"?>\n\
+                    <root>\n  <!-- a\n   comment -->\n  <p>Some <b>bold</b>  text</p>\n  \
+                    <code xml:space=\"preserve\">\n  <x>  </x>\n</code>\n  \
+                    <script><![CDATA[ if (a < b) {} ]]></script>\n  \
+                    <v> spaced &amp; text </v> <!-- inline -->\n</root>\n";
+        assert_eq!(format_xml(input), want);
+    }
+
+    #[test]
+    fn test_errors() {
+        let xml = Xml::default();
+        assert_eq!(
+            xml.format("<a>\n  <b></c>\n</a>").unwrap_err(),
+            "mismatched closing tag </c> at line 2 column 6, expected </b>"
+        );
+        assert_eq!(
+            xml.format("<a>\n  <b>").unwrap_err(),
+            "unclosed element <b> at line 2 column 3"
+        );
+        assert_eq!(
+            xml.format("<a b=c></a>").unwrap_err(),
+            "invalid attribute at line 1 column 3: expected quote mark not 'c' \
+             at line 1 column 6"
+        );
+    }
+}
@@ -1,41 +1,431 @@
-use super::Format;
+use std::{path::Path, sync::Arc};
+
+use serde::Deserialize;
+use yaml_rust2::{
+    parser::{Event, MarkedEventReceiver, Parser},
+    scanner::Marker,
+};
+
+use super::{
+    duplicates::{Checker, DuplicateKeys},
+    shift_lines, with_linked, Format, Formatters,
+};
+
+/// Yaml formats YAML documents, along with any block scalars whose content is
+/// marked as another language that metafmt can format.
+#[derive(Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Yaml {
+    /// Reports keys that appear more than once in the same mapping.
+    pub duplicate_keys: DuplicateKeys,
+    /// Literal block scalars whose content is formatted as another language,
+    /// matched by their key path. The first matching rule applies.
+    pub embedded: Vec<Embedded>,
+
+    /// The formatters used for embedded content, which are configured along
+    /// with this one. See `Formatters::link`.
+    #[serde(skip)]
+    formatters: Option<Arc<Formatters>>,
+}
 
-#[derive(Clone, Copy, Default)]
-pub(crate) struct Yaml {}
+/// Embedded maps the literal block scalars at a key path to a language.
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Embedded {
+    /// A dotted key path, e.g. "jobs.*.steps.*.run". A "*" matches any
+    /// characters within a key or sequence index, and keys containing dots
+    /// are quoted, e.g. `data."*.json"`.
+    pub path: String,
+    /// The language of the content, as a file extension or format name.
+    pub language: String,
+}
 
 impl Format for Yaml {
     fn format(&self, input: &str) -> Result<String, String> {
-        format_yaml(input)
+        self.format_yaml(input).map(|(out, _)| out)
+    }
+
+    fn warnings(&self, _path: Option<&Path>, input: &str) -> Vec<String> {
+        self.format_yaml(input)
+            .map(|(_, warnings)| warnings)
+            .unwrap_or_default()
+    }
+
+    fn format_with_warnings(
+        &self,
+        _path: Option<&Path>,
+        input: &str,
+    ) -> Result<(String, Vec<String>), String> {
+        self.format_yaml(input)
     }
 }
 
 const INDENT_WIDTH: usize = 2;
 const MAX_LINE_LENGTH: usize = 100;
 
-fn format_yaml(input: &str) -> Result<String, String> {
-    if input.is_empty() {
-        return Ok(String::new());
+/// The prefix of a comment that marks the language of a block scalar, e.g.
+/// "# metafmt: json".
+const MARKER: &str = "metafmt:";
+
+impl Yaml {
+    /// Sets the formatters used for embedded content.
+    pub(super) fn set_formatters(&mut self, formatters: Arc<Formatters>) {
+        self.formatters = Some(formatters);
+    }
+
+    /// Formats a document, returning it along with its warnings: duplicate
+    /// keys that are configured as warnings, then the problems found in its
+    /// embedded content.
+    fn format_yaml(&self, input: &str) -> Result<(String, Vec<String>), String> {
+        if input.is_empty() {
+            return Ok((String::new(), Vec::new()));
+        }
+
+        // Validate YAML syntax using yaml-rust2.
+        let mut warnings = validate_yaml(input)?;
+        if self.duplicate_keys == DuplicateKeys::Error && !warnings.is_empty() {
+            return Err(warnings.join("\n"));
+        }
+
+        // Normalize line endings to LF.
+        let normalized = input.replace("\r\n", "\n").replace('\r', "\n");
+
+        // Tokenize lines.
+        let mut tokens = tokenize(&normalized);
+
+        // Format embedded content in place.
+        if self.has_embedded(&normalized) {
+            warnings.extend(self.format_embedded(&normalized, &mut tokens));
+        }
+
+        // Emit formatted output.
+        let output = emit(&tokens);
+
+        Ok((output, warnings))
+    }
+
+    /// Returns true if any block scalar of the input may be marked with a
+    /// language.
+    fn has_embedded(&self, input: &str) -> bool {
+        !self.embedded.is_empty() || input.contains(MARKER)
+    }
+
+    /// Formats the content of the literal block scalars that are marked with a
+    /// language, either by a "# metafmt: <language>" comment on or above
+    /// their key or by an embedded rule, returning the problems found in
+    /// them. Content that fails to format is left as written.
+    fn format_embedded(&self, input: &str, tokens: &mut Vec<Token>) -> Vec<String> {
+        let lines: Vec<&str> = input.split('\n').collect();
+        let rules: Vec<(Vec<String>, &str)> = self
+            .embedded
+            .iter()
+            .map(|rule| (split_path(&rule.path), rule.language.as_str()))
+            .collect();
+        let mut path = KeyPath::default();
+        let mut problems = Vec::new();
+        let mut i = 0;
+
+        while i < tokens.len() {
+            let (line, lang) = match &tokens[i] {
+                Token::DocumentStart { .. } | Token::DocumentEnd { .. } => {
+                    path.clear();
+                    (0, None)
+                }
+                Token::MappingKey { indent, key, .. } => {
+                    path.key(*indent, unquote(key));
+                    (0, None)
+                }
+                Token::SequenceEntry { indent, .. } => {
+                    path.item(*indent);
+                    (0, None)
+                }
+                Token::BlockScalarHeader {
+                    header,
+                    inline_comment,
+                    line,
+                    ..
+                } if is_literal(header) => {
+                    let comments = lines[..*line]
+                        .iter()
+                        .rev()
+                        .map(|line| line.trim())
+                        .take_while(|line| line.starts_with('#'));
+                    let lang = inline_comment
+                        .as_deref()
+                        .and_then(marker)
+                        .or_else(|| comments.filter_map(marker).next())
+                        .or_else(|| {
+                            rules
+                                .iter()
+                                .find(|(pattern, _)| path.matches(pattern))
+                                .map(|(_, lang)| lang.to_lowercase())
+                        });
+                    (*line, lang)
+                }
+                _ => (0, None),
+            };
+            i += 1;
+            let Some(lang) = lang else {
+                continue;
+            };
+
+            let end = tokens[i..]
+                .iter()
+                .position(|token| !matches!(token, Token::BlockScalarLine { .. }))
+                .map_or(tokens.len(), |n| i + n);
+            match self.format_block(&lang, &tokens[i..end]) {
+                Some(Ok(block)) => {
+                    let len = block.len();
+                    tokens.splice(i..end, block);
+                    i += len;
+                }
+                Some(Err((err, offset))) => {
+                    problems.push(format!(
+                        "{lang} block scalar at line {}: {}",
+                        line + 1,
+                        shift_lines(&err, line + 1 + offset)
+                    ));
+                    i = end;
+                }
+                None => i = end,
+            }
+        }
+        problems
     }
 
-    // Validate YAML syntax using yaml-rust2.
-    validate_yaml(input)?;
+    /// Formats the lines of a block scalar as the language, returning None if
+    /// metafmt has no formatter for it or the formatted content can't be
+    /// written as the same block scalar. Errors are returned along with the
+    /// number of blank lines that precede the content.
+    fn format_block(
+        &self,
+        lang: &str,
+        block: &[Token],
+    ) -> Option<Result<Vec<Token>, (String, usize)>> {
+        let texts: Vec<&str> = block
+            .iter()
+            .map(|token| match token {
+                Token::BlockScalarLine { text } => text.as_str(),
+                _ => "",
+            })
+            .collect();
+        // Blank lines around the content are kept as written, as they are
+        // part of the scalar's value with the "+" chomping indicator.
+        let start = texts.iter().position(|text| !text.is_empty())?;
+        let end = texts.iter().rposition(|text| !text.is_empty())? + 1;
+        let base = texts[start].len() - texts[start].trim_start_matches(' ').len();
+
+        let mut content = String::new();
+        for text in &texts[start..end] {
+            let indent = text.len() - text.trim_start_matches(' ').len();
+            content.push_str(&text[indent.min(base)..]);
+            content.push('\n');
+        }
+
+        let result = with_linked(self.formatters.as_deref(), |formatters| {
+            formatters
+                .for_embedded(lang)
+                .map(|formatter| formatter.format(&content))
+        })?;
+        let out = match result {
+            Ok(out) => out,
+            Err(err) => return Some(Err((err, start))),
+        };
+        // The first line of the content determines the scalar's indentation,
+        // so it can't start with a space.
+        let out = out.trim_end_matches('\n');
+        if out.trim().is_empty() || out.trim_start_matches('\n').starts_with(' ') {
+            return None;
+        }
+
+        let indent = " ".repeat(base);
+        let lines = out.split('\n').map(|line| Token::BlockScalarLine {
+            text: match line.is_empty() {
+                true => String::new(),
+                false => format!("{indent}{line}"),
+            },
+        });
+        let mut tokens = block[..start].to_vec();
+        tokens.extend(lines);
+        tokens.extend_from_slice(&block[end..]);
+        Some(Ok(tokens))
+    }
+}
+
+/// KeyPath tracks the keys and sequence indexes that lead to each token of a
+/// document.
+#[derive(Default)]
+struct KeyPath {
+    /// The indent and key of each enclosing mapping entry, or the indent and
+    /// index of each enclosing sequence item.
+    entries: Vec<(usize, Segment)>,
+}
+
+enum Segment {
+    Key(String),
+    Index(usize),
+}
 
-    // Normalize line endings to LF.
-    let normalized = input.replace("\r\n", "\n").replace('\r', "\n");
+impl KeyPath {
+    fn clear(&mut self) {
+        self.entries.clear();
+    }
 
-    // Tokenize lines.
-    let tokens = tokenize(&normalized);
+    /// Enters the mapping entry at the indent, leaving any sibling entries.
+    fn key(&mut self, indent: usize, key: String) {
+        while self.entries.last().is_some_and(|(i, _)| *i >= indent) {
+            self.entries.pop();
+        }
+        self.entries.push((indent, Segment::Key(key)));
+    }
 
-    // Emit formatted output.
-    let output = emit(&tokens);
+    /// Enters the sequence item at the indent, following the previous item.
+    /// Items may have the same indent as the key that holds the sequence.
+    fn item(&mut self, indent: usize) {
+        while self.entries.last().is_some_and(|(i, _)| *i > indent) {
+            self.entries.pop();
+        }
+        let index = match self.entries.last() {
+            Some((i, Segment::Index(index))) if *i == indent => {
+                let index = index + 1;
+                self.entries.pop();
+                index
+            }
+            _ => 0,
+        };
+        self.entries.push((indent, Segment::Index(index)));
+    }
 
-    Ok(output)
+    /// Returns true if every segment of the path matches the pattern's.
+    fn matches(&self, pattern: &[String]) -> bool {
+        pattern.len() == self.entries.len()
+            && pattern
+                .iter()
+                .zip(&self.entries)
+                .all(|(pattern, (_, segment))| match segment {
+                    Segment::Key(key) => glob(pattern, key),
+                    Segment::Index(index) => glob(pattern, &index.to_string()),
+                })
+    }
 }
 
-fn validate_yaml(input: &str) -> Result<(), String> {
-    yaml_rust2::YamlLoader::load_from_str(input)
-        .map(|_| ())
-        .map_err(|err| err.to_string())
+/// Splits a dotted key path into its segments, removing the quotes around
+/// any that contain dots.
+fn split_path(path: &str) -> Vec<String> {
+    let mut segments = vec![String::new()];
+    let mut quoted = false;
+    for c in path.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            '.' if !quoted => segments.push(String::new()),
+            c => segments.last_mut().unwrap().push(c),
+        }
+    }
+    segments
+}
+
+/// Returns true if the text matches the pattern, in which a "*" matches any
+/// characters.
+fn glob(pattern: &str, text: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == text,
+        Some((prefix, rest)) => text.strip_prefix(prefix).is_some_and(|text| {
+            text.char_indices()
+                .map(|(i, _)| i)
+                .chain([text.len()])
+                .any(|i| glob(rest, &text[i..]))
+        }),
+    }
+}
+
+/// Returns the value of a quoted key, or the key as written.
+fn unquote(key: &str) -> String {
+    if let Some(key) = key.strip_prefix('"').and_then(|k| k.strip_suffix('"')) {
+        key.replace("\\\"", "\"").replace("\\\\", "\\")
+    } else if let Some(key) = key.strip_prefix('\'').and_then(|k| k.strip_suffix('\'')) {
+        key.replace("''", "'")
+    } else {
+        key.to_string()
+    }
+}
+
+/// Returns true for the header of a literal block scalar without an
+/// indentation indicator, whose lines are kept exactly as written.
+fn is_literal(header: &str) -> bool {
+    matches!(header, "|" | "|-" | "|+")
+}
+
+/// Returns the language of a "# metafmt: <language>" comment.
+fn marker(comment: &str) -> Option<String> {
+    let rest = comment
+        .strip_prefix('#')?
+        .trim_start()
+        .strip_prefix(MARKER)?;
+    // The language ends at whitespace or punctuation, e.g. "# metafmt: sql, dbt".
+    let rest = rest.trim_start();
+    let end = rest
+        .find(|c: char| !c.is_alphanumeric() && !matches!(c, '-' | '_' | '+'))
+        .unwrap_or(rest.len());
+    match &rest[..end] {
+        "" => None,
+        language => Some(language.to_lowercase()),
+    }
+}
+
+/// Parses the input, returning a message for every duplicate key.
+fn validate_yaml(input: &str) -> Result<Vec<String>, String> {
+    let mut receiver = KeyReceiver::default();
+    Parser::new_from_str(input)
+        .load(&mut receiver, true)
+        .map_err(|err| err.to_string())?;
+    Ok(receiver.checker.finish())
+}
+
+/// KeyReceiver records the keys of every mapping as the parser produces them.
+#[derive(Default)]
+struct KeyReceiver {
+    checker: Checker,
+    /// Whether the next node of each open mapping is a key, or None for
+    /// sequences.
+    frames: Vec<Option<bool>>,
+}
+
+impl KeyReceiver {
+    /// Moves the innermost mapping between its keys and values once a node
+    /// has ended.
+    fn end_node(&mut self) {
+        if let Some(Some(expect_key)) = self.frames.last_mut() {
+            *expect_key = !*expect_key;
+        }
+    }
+}
+
+impl MarkedEventReceiver for KeyReceiver {
+    fn on_event(&mut self, ev: Event, mark: Marker) {
+        match ev {
+            Event::Scalar(value, ..) => {
+                if let Some(Some(true)) = self.frames.last() {
+                    self.checker.key(&value, mark.line(), mark.col() + 1);
+                }
+                self.end_node();
+            }
+            Event::Alias(_) => self.end_node(),
+            Event::MappingStart(..) => {
+                self.checker.open(true);
+                self.frames.push(Some(true));
+            }
+            Event::SequenceStart(..) => {
+                self.checker.open(false);
+                self.frames.push(None);
+            }
+            Event::MappingEnd | Event::SequenceEnd => {
+                self.checker.close();
+                self.frames.pop();
+                self.end_node();
+            }
+            _ => {}
+        }
+    }
 }
 
 // --- Tokenizer ---
@@ -70,6 +460,8 @@ enum Token {
         indent: usize,
         header: String,
         inline_comment: Option<String>,
+        /// The 0-based line that the header is on.
+        line: usize,
     },
     BlockScalarLine {
         text: String,
@@ -103,10 +495,8 @@ fn find_inline_comment(s: &str) -> Option<usize> {
             b'\\' if in_double_quote => {
                 i += 1; // skip escaped char
             }
-            b'#' if !in_single_quote && !in_double_quote => {
-                if i > 0 && bytes[i - 1] == b' ' {
-                    return Some(i);
-                }
+            b'#' if !in_single_quote && !in_double_quote && i > 0 && bytes[i - 1] == b' ' => {
+                return Some(i);
             }
             _ => {}
         }
@@ -247,6 +637,7 @@ fn tokenize(input: &str) -> Vec<Token> {
                         indent: indent + 2,
                         header,
                         inline_comment: comment.or(inline_comment),
+                        line: i,
                     });
                     context = Context::BlockScalar {
                         parent_indent: indent + 2 - 1,
@@ -322,6 +713,7 @@ fn tokenize(input: &str) -> Vec<Token> {
                                 indent: mk_indent + INDENT_WIDTH,
                                 header,
                                 inline_comment: hdr_comment.or(mk_comment),
+                                line: i,
                             });
                             context = Context::BlockScalar {
                                 parent_indent: mk_indent + INDENT_WIDTH - 1,
@@ -384,6 +776,7 @@ fn tokenize(input: &str) -> Vec<Token> {
                         indent: indent + INDENT_WIDTH,
                         header,
                         inline_comment: hdr_comment.or(inline_comment),
+                        line: i,
                     });
                     context = Context::BlockScalar {
                         parent_indent: indent + INDENT_WIDTH - 1,
@@ -457,15 +850,14 @@ fn find_mapping_colon(s: &str) -> Option<usize> {
             b'}' if !in_single_quote && !in_double_quote => brace_depth -= 1,
             b'[' if !in_single_quote && !in_double_quote => bracket_depth += 1,
             b']' if !in_single_quote && !in_double_quote => bracket_depth -= 1,
+            // Colon must be followed by space, tab, or be at end.
             b':' if !in_single_quote
                 && !in_double_quote
                 && brace_depth == 0
-                && bracket_depth == 0 =>
+                && bracket_depth == 0
+                && (i + 1 >= bytes.len() || bytes[i + 1] == b' ' || bytes[i + 1] == b'\t') =>
             {
-                // Colon must be followed by space, tab, or be at end.
-                if i + 1 >= bytes.len() || bytes[i + 1] == b' ' || bytes[i + 1] == b'\t' {
-                    return Some(i);
-                }
+                return Some(i);
             }
             _ => {}
         }
@@ -630,6 +1022,7 @@ fn emit(tokens: &[Token]) -> String {
                         header,
                         inline_comment: hdr_comment,
                         indent: hdr_indent,
+                        ..
                     }) = tokens.get(i + 1)
                     {
                         // Emit "key: |" on the same line.
@@ -736,6 +1129,7 @@ fn emit(tokens: &[Token]) -> String {
                                         header,
                                         inline_comment: hdr_comment,
                                         indent: hdr_indent,
+                                        ..
                                     }) = tokens.get(i + 2)
                                     {
                                         line.push(' ');
@@ -750,7 +1144,10 @@ fn emit(tokens: &[Token]) -> String {
                                         output.push('\n');
                                         in_block_scalar = true;
                                         block_scalar_base_indent = None;
-                                        block_scalar_canonical_indent = canonical_indent;
+                                        // The content is indented past the
+                                        // key, which follows the "- ".
+                                        block_scalar_canonical_indent =
+                                            canonical_indent + INDENT_WIDTH;
                                         mapper.depth_for(*hdr_indent);
                                         i += 3;
                                         prev_was_blank = false;
@@ -776,6 +1173,7 @@ fn emit(tokens: &[Token]) -> String {
                                 header,
                                 inline_comment: hdr_comment,
                                 indent: hdr_indent,
+                                ..
                             } => {
                                 // "- |" on the same line.
                                 line.push(' ');
@@ -807,6 +1205,7 @@ fn emit(tokens: &[Token]) -> String {
                 indent,
                 header,
                 inline_comment,
+                ..
             } => {
                 // This case handles standalone block scalar headers that weren't
                 // consumed by the mapping key or sequence entry handling above.
@@ -830,7 +1229,9 @@ fn emit(tokens: &[Token]) -> String {
                 if text.is_empty() {
                     output.push('\n');
                 } else {
-                    let raw_indent = measure_indent(text);
+                    // Only spaces indent a line; tabs are part of the content.
+                    let content = text.trim_start_matches(' ');
+                    let raw_indent = text.len() - content.len();
                     if block_scalar_base_indent.is_none() {
                         block_scalar_base_indent = Some(raw_indent);
                     }
@@ -838,7 +1239,7 @@ fn emit(tokens: &[Token]) -> String {
                     let extra = raw_indent.saturating_sub(base);
                     let new_indent = block_scalar_canonical_indent + INDENT_WIDTH + extra;
                     write_indent(&mut output, new_indent);
-                    output.push_str(text.trim_start());
+                    output.push_str(content);
                     output.push('\n');
                 }
                 prev_was_blank = false;
@@ -888,6 +1289,10 @@ fn can_break_value(val: &str) -> bool {
 mod tests {
     use super::*;
 
+    fn format_yaml(input: &str) -> Result<String, String> {
+        Yaml::default().format(input)
+    }
+
     #[test]
     fn test_empty_input() {
         assert_eq!(format_yaml("").unwrap(), "");
@@ -1026,4 +1431,121 @@ mod tests {
             "defaults: &defaults\n  adapter: postgres\nproduction:\n  database: myapp\n  adapter: postgres\n"
         );
     }
+
+    #[test]
+    fn test_duplicate_keys() {
+        let input = "a: 1\nb:\n  c: 1\n  d: [{c: 1}, {c: 2}]\n  c: 2\n---\na: 3\n\"a\": 4\n";
+        assert_eq!(
+            format_yaml(input).unwrap_err(),
+            "duplicate key \"c\" at line 5 column 3, first defined at line 3 column 3\n\
+             duplicate key \"a\" at line 8 column 1, first defined at line 7 column 1"
+        );
+
+        let yaml = Yaml {
+            duplicate_keys: DuplicateKeys::Warn,
+            ..Yaml::default()
+        };
+        assert!(yaml.format(input).is_ok());
+        assert_eq!(yaml.warnings(None, input).len(), 2);
+        assert!(Yaml::default().warnings(None, input).is_empty());
+    }
+
+    #[test]
+    fn test_block_scalar_in_sequence() {
+        let input = "steps:\n  - run: |\n      make\n    name: x\n  - |\n    text\n";
+        let result = format_yaml(input).unwrap();
+        assert_eq!(result, input);
+    }
+
+    #[test]
+    fn test_block_scalar_tabs() {
+        let input = "make: |\n    all:\n    \techo ok\n";
+        let result = format_yaml(input).unwrap();
+        assert_eq!(result, "make: |\n  all:\n  \techo ok\n");
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_embedded_marker() {
+        let input = "config: |  # metafmt: json\n    {\"a\":1,\n    \"b\":[1,2]}\n\
+                     # metafmt: JSON\n\
+                     other: |-\n  {\"c\":  true}\n\n\
+                     kept: |+\n  # metafmt: json\n  [1,\n  2]\n\n\n\
+                     plain: |\n  {\"a\":1}\n";
+        let want = "config: | # metafmt: json\n  {\n    \"a\": 1,\n    \"b\": [1, 2]\n  }\n\
+                    # metafmt: JSON\n\
+                    other: |-\n  {\n    \"c\": true\n  }\n\n\
+                    kept: |+\n  # metafmt: json\n  [1,\n  2]\n\n\n\
+                    plain: |\n  {\"a\":1}\n";
+        let result = format_yaml(input).unwrap();
+        assert_eq!(result, want);
+        assert_eq!(format_yaml(&result).unwrap(), want);
+    }
+
+    #[test]
+    fn test_embedded_rules() {
+        let formatters = Formatters {
+            yaml: Yaml {
+                embedded: vec![
+                    Embedded {
+                        path: "data.\"*.txt\"".to_string(),
+                        language: "txt".to_string(),
+                    },
+                    Embedded {
+                        path: "jobs.*.steps.*.run".to_string(),
+                        language: "txt".to_string(),
+                    },
+                ],
+                ..Yaml::default()
+            },
+            external: vec![super::super::external::External {
+                extensions: vec!["txt".to_string()],
+                command: "tr".to_string(),
+                args: vec!["a-z".to_string(), "A-Z".to_string()],
+                ..Default::default()
+            }],
+            ..Formatters::default()
+        }
+        .link();
+        let input = "data:\n  a.txt: |\n    hello\n  a.md: |\n    hello\n\
+                     jobs:\n  build:\n    steps:\n    - name: test\n      run: |\n        \
+                     make test\n    - run: |+\n        make\n\n    - uses: x\n      with:\n        \
+                     run: |\n          make\n";
+        let want = "data:\n  a.txt: |\n    HELLO\n  a.md: |\n    hello\n\
+                    jobs:\n  build:\n    steps:\n    - name: test\n      run: |\n        \
+                    MAKE TEST\n    - run: |+\n        MAKE\n\n    - uses: x\n      with:\n        \
+                    run: |\n          make\n";
+        assert_eq!(formatters.yaml.format(input).unwrap(), want);
+    }
+
+    #[test]
+    fn test_embedded_problems() {
+        let input = "a: 1\nb: | # metafmt: yaml\n\n  c: [1\n  d: 2\n";
+        let yaml = Yaml::default();
+        assert_eq!(yaml.format(input).unwrap(), input);
+        let warnings = yaml.warnings(None, input);
+        assert_eq!(warnings.len(), 1, "{warnings:?}");
+        assert!(
+            warnings[0].starts_with("yaml block scalar at line 2: "),
+            "{warnings:?}"
+        );
+        assert!(warnings[0].contains("line 5"), "{warnings:?}");
+        assert_eq!(
+            yaml.format_with_warnings(None, input).unwrap(),
+            (input.to_string(), warnings)
+        );
+    }
+
+    #[test]
+    fn test_key_path() {
+        assert_eq!(split_path("data.\"*.json\""), ["data", "*.json"]);
+        assert!(glob("*.json", "app.json"));
+        assert!(glob("a*b*c", "abxbc"));
+        assert!(!glob("*.json", "app.yaml"));
+        assert_eq!(unquote("'it''s'"), "it's");
+        assert_eq!(marker("# metafmt: SQL, dbt").as_deref(), Some("sql"));
+        assert_eq!(marker("#metafmt:json5").as_deref(), Some("json5"));
+        assert_eq!(marker("# metafmt: c++; x").as_deref(), Some("c++"));
+        assert_eq!(marker("# metafmt: (json)"), None);
+    }
 }
@@ -0,0 +1,12 @@
+//! JavaScript bindings for the formatters.
+
+use wasm_bindgen::prelude::*;
+
+/// Formats the input using the formatter for `kind`, which is either a path
+/// or a filetype name (e.g. "yaml"). `options` may hold a JSON object with
+/// the options for each formatter, except external formatters.
+#[wasm_bindgen]
+pub fn format(kind: &str, input: &str, options: Option<String>) -> Result<String, JsValue> {
+    crate::format_with_json_options(kind, input, options.as_deref())
+        .map_err(|err| JsValue::from_str(&err))
+}
@@ -1,39 +1,10 @@
 use std::fs;
 use std::path::Path;
 
-// Import the Format trait and Yaml formatter by re-using the binary's code
-// Since the types module is pub(crate), we test via the public binary interface.
-// Instead, we replicate the formatting function for testing.
+use metafmt::{format_str, Formatters};
 
 fn format_yaml(input: &str) -> Result<String, String> {
-    // We test by invoking the binary via stdin.
-    use std::io::Write;
-    use std::process::{Command, Stdio};
-
-    let mut child = Command::new(env!("CARGO_BIN_EXE_metafmt"))
-        .args(["--stdin-filetype", "yaml", "-"])
-        .stdin(Stdio::piped())
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()
-        .expect("failed to spawn metafmt");
-
-    child
-        .stdin
-        .as_mut()
-        .unwrap()
-        .write_all(input.as_bytes())
-        .expect("failed to write to stdin");
-
-    let output = child
-        .wait_with_output()
-        .expect("failed to wait for metafmt");
-
-    if output.status.success() {
-        Ok(String::from_utf8(output.stdout).expect("invalid utf8 output"))
-    } else {
-        Err(String::from_utf8(output.stderr).expect("invalid utf8 stderr"))
-    }
+    format_str("yaml", input, &Formatters::default())
 }
 
 fn run_fixture(name: &str) {